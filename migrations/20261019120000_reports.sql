@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS "report_settings" (
+	"chat_id" BIGINT PRIMARY KEY,
+	"enabled" BOOLEAN NOT NULL DEFAULT TRUE,
+	CONSTRAINT "fk_report_settings" FOREIGN KEY ("chat_id") REFERENCES "chats" ("chat_id")
+);
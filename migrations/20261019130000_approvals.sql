@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS "approvals" (
+	"chat_id" BIGINT,
+	"user_id" BIGINT,
+	PRIMARY KEY("chat_id", "user_id"),
+	CONSTRAINT "fk_approvals" FOREIGN KEY ("chat_id") REFERENCES "chats" ("chat_id")
+);
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS "night_mode" (
+	"chat_id" BIGINT PRIMARY KEY,
+	"start_time" TIME NOT NULL,
+	"end_time" TIME NOT NULL,
+	"timezone" TEXT NOT NULL,
+	"active" BOOLEAN NOT NULL DEFAULT FALSE,
+	"saved_permissions" SMALLINT,
+	CONSTRAINT "fk_night_mode" FOREIGN KEY ("chat_id") REFERENCES "chats" ("chat_id")
+);
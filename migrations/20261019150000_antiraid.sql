@@ -0,0 +1,15 @@
+CREATE TABLE IF NOT EXISTS "antiraid" (
+	"chat_id" BIGINT PRIMARY KEY,
+	"threshold" INTEGER NOT NULL DEFAULT 0,
+	"ban_duration" BIGINT NOT NULL DEFAULT 3600,
+	"raid_duration" BIGINT NOT NULL DEFAULT 21600,
+	"raid_until" TIMESTAMPTZ,
+	CONSTRAINT "fk_antiraid" FOREIGN KEY ("chat_id") REFERENCES "chats" ("chat_id")
+);
+
+CREATE TABLE IF NOT EXISTS "raiders" (
+	"chat_id" BIGINT,
+	"user_id" BIGINT,
+	PRIMARY KEY("chat_id", "user_id"),
+	CONSTRAINT "fk_raiders" FOREIGN KEY ("chat_id") REFERENCES "chats" ("chat_id")
+);
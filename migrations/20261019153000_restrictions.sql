@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS "restrictions" (
+	"chat_id" BIGINT,
+	"user_id" BIGINT,
+	"revoked_permissions" SMALLINT NOT NULL,
+	PRIMARY KEY("chat_id", "user_id"),
+	CONSTRAINT "fk_restrictions" FOREIGN KEY ("chat_id") REFERENCES "chats" ("chat_id")
+);
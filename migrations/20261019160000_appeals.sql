@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS "appeals" (
+	"appeal_id" SERIAL PRIMARY KEY,
+	"chat_id" BIGINT NOT NULL,
+	"user_id" BIGINT NOT NULL,
+	"reason" TEXT,
+	"status" TEXT NOT NULL DEFAULT 'pending',
+	"created_at" TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+	CONSTRAINT "fk_appeals" FOREIGN KEY ("chat_id") REFERENCES "chats" ("chat_id")
+);
@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS "timezones" (
+	"chat_id" BIGINT PRIMARY KEY,
+	"timezone" TEXT NOT NULL,
+	CONSTRAINT "fk_timezones" FOREIGN KEY ("chat_id") REFERENCES "chats" ("chat_id")
+);
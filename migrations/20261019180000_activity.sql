@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS "activity" (
+	"chat_id" BIGINT,
+	"user_id" BIGINT,
+	"last_seen" TIMESTAMPTZ NOT NULL,
+	PRIMARY KEY("chat_id", "user_id"),
+	CONSTRAINT "fk_activity" FOREIGN KEY ("chat_id") REFERENCES "chats" ("chat_id")
+);
+
+CREATE INDEX "idx_activity_last_seen" ON "activity" ("chat_id", "last_seen");
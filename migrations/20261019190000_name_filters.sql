@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS "name_filters" (
+	"chat_id" BIGINT,
+	"pattern" TEXT,
+	"action" TEXT NOT NULL,
+	PRIMARY KEY("chat_id", "pattern"),
+	CONSTRAINT "fk_name_filters" FOREIGN KEY ("chat_id") REFERENCES "chats" ("chat_id")
+);
@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS "antichannel" (
+	"chat_id" BIGINT PRIMARY KEY,
+	"enabled" BOOLEAN NOT NULL DEFAULT FALSE,
+	CONSTRAINT "fk_antichannel" FOREIGN KEY ("chat_id") REFERENCES "chats" ("chat_id")
+);
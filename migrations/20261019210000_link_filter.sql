@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS "link_settings" (
+	"chat_id" BIGINT PRIMARY KEY,
+	"mode" TEXT NOT NULL DEFAULT 'off',
+	"action" TEXT,
+	CONSTRAINT "fk_link_settings" FOREIGN KEY ("chat_id") REFERENCES "chats" ("chat_id")
+);
+
+CREATE TABLE IF NOT EXISTS "link_domains" (
+	"chat_id" BIGINT,
+	"domain" TEXT,
+	"allowed" BOOLEAN NOT NULL,
+	PRIMARY KEY("chat_id", "domain"),
+	CONSTRAINT "fk_link_domains" FOREIGN KEY ("chat_id") REFERENCES "chats" ("chat_id")
+);
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS "script_filters" (
+	"chat_id" BIGINT,
+	"script" TEXT,
+	"threshold" INTEGER NOT NULL,
+	"action" TEXT,
+	PRIMARY KEY("chat_id", "script"),
+	CONSTRAINT "fk_script_filters" FOREIGN KEY ("chat_id") REFERENCES "chats" ("chat_id")
+);
@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS "blocked_media" (
+	"chat_id" BIGINT,
+	"file_unique_id" TEXT,
+	"media_type" TEXT NOT NULL,
+	"action" TEXT,
+	PRIMARY KEY("chat_id", "file_unique_id"),
+	CONSTRAINT "fk_blocked_media" FOREIGN KEY ("chat_id") REFERENCES "chats" ("chat_id")
+);
+
+CREATE TABLE IF NOT EXISTS "global_blocked_media" (
+	"file_unique_id" TEXT PRIMARY KEY,
+	"media_type" TEXT NOT NULL,
+	"action" TEXT
+);
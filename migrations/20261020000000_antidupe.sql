@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS "antidupe" (
+	"chat_id" BIGINT PRIMARY KEY,
+	"repeat_threshold" INTEGER NOT NULL DEFAULT 0,
+	"user_threshold" INTEGER NOT NULL DEFAULT 0,
+	"window_duration" BIGINT NOT NULL DEFAULT 60,
+	"mute_duration" BIGINT NOT NULL DEFAULT 3600,
+	CONSTRAINT "fk_antidupe" FOREIGN KEY ("chat_id") REFERENCES "chats" ("chat_id")
+);
@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS "roles" (
+	"chat_id" BIGINT,
+	"user_id" BIGINT,
+	"role" TEXT NOT NULL,
+	PRIMARY KEY("chat_id", "user_id"),
+	CONSTRAINT "fk_roles" FOREIGN KEY ("chat_id") REFERENCES "chats" ("chat_id")
+);
@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS "disabled_commands" (
+	"chat_id" BIGINT,
+	"command" TEXT,
+	PRIMARY KEY("chat_id", "command"),
+	CONSTRAINT "fk_disabled_commands" FOREIGN KEY ("chat_id") REFERENCES "chats" ("chat_id")
+);
+
+CREATE TABLE IF NOT EXISTS "command_settings" (
+	"chat_id" BIGINT PRIMARY KEY,
+	"delete_disabled" BOOLEAN NOT NULL DEFAULT FALSE,
+	"exempt_admins" BOOLEAN NOT NULL DEFAULT TRUE,
+	CONSTRAINT "fk_command_settings" FOREIGN KEY ("chat_id") REFERENCES "chats" ("chat_id")
+);
@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS "connections" (
+	"user_id" BIGINT PRIMARY KEY,
+	"chat_id" BIGINT NOT NULL,
+	CONSTRAINT "fk_connections" FOREIGN KEY ("chat_id") REFERENCES "chats" ("chat_id")
+);
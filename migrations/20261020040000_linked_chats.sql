@@ -0,0 +1,9 @@
+CREATE SEQUENCE IF NOT EXISTS "linked_chats_link_id_seq";
+
+CREATE TABLE IF NOT EXISTS "linked_chats" (
+	"chat_id" BIGINT PRIMARY KEY,
+	"link_id" BIGINT NOT NULL,
+	CONSTRAINT "fk_linked_chats" FOREIGN KEY ("chat_id") REFERENCES "chats" ("chat_id")
+);
+
+CREATE INDEX "idx_linked_chats_link_id" ON "linked_chats" ("link_id");
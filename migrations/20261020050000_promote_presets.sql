@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS "promote_presets" (
+	"chat_id" BIGINT NOT NULL,
+	"name" TEXT NOT NULL,
+	"can_manage_chat" BOOLEAN NOT NULL DEFAULT FALSE,
+	"can_change_info" BOOLEAN NOT NULL DEFAULT FALSE,
+	"can_delete_messages" BOOLEAN NOT NULL DEFAULT FALSE,
+	"can_manage_voice_chats" BOOLEAN NOT NULL DEFAULT FALSE,
+	"can_invite_users" BOOLEAN NOT NULL DEFAULT FALSE,
+	"can_restrict_members" BOOLEAN NOT NULL DEFAULT FALSE,
+	"can_pin_messages" BOOLEAN NOT NULL DEFAULT FALSE,
+	"can_promote_members" BOOLEAN NOT NULL DEFAULT FALSE,
+	PRIMARY KEY ("chat_id", "name"),
+	CONSTRAINT "fk_promote_presets" FOREIGN KEY ("chat_id") REFERENCES "chats" ("chat_id")
+);
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS "report_copies" (
+	"chat_id" BIGINT NOT NULL,
+	"message_id" INT NOT NULL,
+	"admin_id" BIGINT NOT NULL,
+	"copy_id" INT NOT NULL,
+	PRIMARY KEY ("chat_id", "message_id", "admin_id"),
+	CONSTRAINT "fk_report_copies" FOREIGN KEY ("chat_id") REFERENCES "chats" ("chat_id")
+);
@@ -1,3 +1,5 @@
+use chrono::{DateTime, NaiveTime, Utc};
+
 pub struct User {
     pub user_id: i64,
     pub full_name: String,
@@ -14,3 +16,81 @@ pub struct Note {
     pub note_id: String,
     pub note_content: String,
 }
+
+pub struct NightMode {
+    pub chat_id: i64,
+    pub start_time: NaiveTime,
+    pub end_time: NaiveTime,
+    pub timezone: String,
+    pub active: bool,
+    pub saved_permissions: Option<i16>,
+}
+
+pub struct AntiRaid {
+    pub chat_id: i64,
+    pub threshold: i32,
+    pub ban_duration: i64,
+    pub raid_duration: i64,
+    pub raid_until: Option<DateTime<Utc>>,
+}
+
+pub struct Appeal {
+    pub appeal_id: i32,
+    pub chat_id: i64,
+    pub user_id: i64,
+    pub reason: Option<String>,
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+}
+
+pub struct NameFilter {
+    pub chat_id: i64,
+    pub pattern: String,
+    pub action: String,
+}
+
+pub struct LinkSettings {
+    pub chat_id: i64,
+    pub mode: String,
+    pub action: Option<String>,
+}
+
+pub struct ScriptFilter {
+    pub chat_id: i64,
+    pub script: String,
+    pub threshold: i32,
+    pub action: Option<String>,
+}
+
+pub struct BlockedMedia {
+    pub file_unique_id: String,
+    pub media_type: String,
+    pub action: Option<String>,
+}
+
+pub struct AntiDupe {
+    pub chat_id: i64,
+    pub repeat_threshold: i32,
+    pub user_threshold: i32,
+    pub window_duration: i64,
+    pub mute_duration: i64,
+}
+
+pub struct CommandSettings {
+    pub chat_id: i64,
+    pub delete_disabled: bool,
+    pub exempt_admins: bool,
+}
+
+pub struct PromotePreset {
+    pub chat_id: i64,
+    pub name: String,
+    pub can_manage_chat: bool,
+    pub can_change_info: bool,
+    pub can_delete_messages: bool,
+    pub can_manage_voice_chats: bool,
+    pub can_invite_users: bool,
+    pub can_restrict_members: bool,
+    pub can_pin_messages: bool,
+    pub can_promote_members: bool,
+}
@@ -1,13 +1,84 @@
+use anyhow::anyhow;
 use sqlx::{Pool, Postgres};
 use teloxide::payloads::SendMessageSetters;
 
-use crate::utils::{self, perms, PinMode};
-use crate::BOT_ID;
+use crate::utils::{self, admincache, perms, PinMode};
+use crate::{entities::PromotePreset, repo::promotepresets, BOT_ID};
 use teloxide::{
     prelude2::*,
-    types::{ChatMember, ChatMemberStatus},
+    types::{ChatMember, ChatMemberKind, ChatMemberStatus},
+    utils::html,
 };
 
+// short names for each admin right, in the order of preset_rights
+const RIGHT_NAMES: [&str; 8] = [
+    "manage", "info", "delete", "voice", "invite", "restrict", "pin", "promote",
+];
+
+// how to tell whether an admin holds each right, in the same order
+const RIGHT_CHECKS: [fn(&ChatMemberKind) -> bool; 8] = [
+    ChatMemberKind::can_manage_chat,
+    ChatMemberKind::can_change_info,
+    ChatMemberKind::can_delete_messages,
+    ChatMemberKind::can_manage_voice_chats,
+    ChatMemberKind::can_invite_users,
+    ChatMemberKind::can_restrict_members,
+    ChatMemberKind::can_pin_messages,
+    ChatMemberKind::can_promote_members,
+];
+
+// position of the right to pin messages in RIGHT_NAMES and RIGHT_CHECKS
+const PIN_RIGHT: usize = 6;
+
+// preset used when /promote isn't given one
+const DEFAULT_PRESET: &str = "mod";
+
+// longest custom title telegram allows for an admin
+const MAX_TITLE_LENGTH: usize = 16;
+
+fn preset_rights(preset: &PromotePreset) -> [bool; 8] {
+    [
+        preset.can_manage_chat,
+        preset.can_change_info,
+        preset.can_delete_messages,
+        preset.can_manage_voice_chats,
+        preset.can_invite_users,
+        preset.can_restrict_members,
+        preset.can_pin_messages,
+        preset.can_promote_members,
+    ]
+}
+
+fn preset_from_rights(chat_id: i64, name: String, rights: [bool; 8]) -> PromotePreset {
+    let [can_manage_chat, can_change_info, can_delete_messages, can_manage_voice_chats, can_invite_users, can_restrict_members, can_pin_messages, can_promote_members] =
+        rights;
+    PromotePreset {
+        chat_id,
+        name,
+        can_manage_chat,
+        can_change_info,
+        can_delete_messages,
+        can_manage_voice_chats,
+        can_invite_users,
+        can_restrict_members,
+        can_pin_messages,
+        can_promote_members,
+    }
+}
+
+fn describe_rights(rights: &[bool; 8]) -> String {
+    let names: Vec<_> = RIGHT_NAMES
+        .iter()
+        .zip(rights)
+        .filter(|(_, &granted)| granted)
+        .map(|(name, _)| *name)
+        .collect();
+    match names.is_empty() {
+        true => "no rights".to_owned(),
+        false => names.join(", "),
+    }
+}
+
 pub async fn promote(
     bot: &crate::Bot,
     message: &Message,
@@ -22,18 +93,52 @@ pub async fn promote(
         perms::require_bot_promote_chat_members(bot, message) // bot requires CAN_PROMOTE_MEMBERS permissions
     )?;
 
-    // extract user ID from message
-    let (user_id, _) = utils::extract_user_and_text(bot, message, pool).await;
-    if user_id.is_none() {
-        // no user was targeted
-        bot.send_message(message.chat.id, "Try targeting a user next time bud.")
-            .reply_to_message_id(message.id)
-            .await?;
+    let promoter_id = match message.from() {
+        Some(user) => user.id,
+        None => return Ok(()),
+    };
+
+    // extract user ID and arguments from message
+    let (user_id, args) = utils::extract_user_and_text(bot, message, pool).await;
+    let user_id = match user_id {
+        Some(id) => id,
+        None => {
+            // no user was targeted
+            bot.send_message(message.chat.id, "Try targeting a user next time bud.")
+                .reply_to_message_id(message.id)
+                .await?;
+            return Ok(());
+        }
+    };
+
+    // the first word picks a preset if it names one, anything else is the admin title
+    let args = args.unwrap_or_default();
+    let args = args.trim();
+    let (first, rest) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+    let (preset, title) =
+        match promotepresets::get_preset(chat.id, &first.to_lowercase(), pool).await? {
+            Some(preset) => (preset, rest.trim()),
+            None => match promotepresets::get_preset(chat.id, DEFAULT_PRESET, pool).await? {
+                Some(preset) => (preset, args),
+                None => return Err(anyhow!("Default promote preset is missing")),
+            },
+        };
+
+    if title.chars().count() > MAX_TITLE_LENGTH {
+        bot.send_message(
+            message.chat.id,
+            format!(
+                "Admin titles can be {} characters long at most.",
+                MAX_TITLE_LENGTH
+            ),
+        )
+        .reply_to_message_id(message.id)
+        .await?;
         return Ok(());
     }
 
     // check if user is valid
-    let user_member: ChatMember = match bot.get_chat_member(chat.id, user_id.unwrap()).await {
+    let user_member: ChatMember = match bot.get_chat_member(chat.id, user_id).await {
         Ok(user) => user,
         Err(_) => {
             bot.send_message(message.chat.id, "This user is ded mate.")
@@ -44,29 +149,294 @@ pub async fn promote(
     };
 
     // user is a dumbass
-    if user_id.unwrap() == *BOT_ID {
+    if user_id == *BOT_ID {
         bot.send_message(message.chat.id, "No u")
             .reply_to_message_id(message.id)
             .await?;
         return Ok(());
     }
 
-    let bot_chat_member: ChatMember = bot.get_chat_member(chat.id, *BOT_ID).await?;
+    let refusal = match user_member.status() {
+        ChatMemberStatus::Owner => {
+            Some("This person CREATED the chat, they can't be promoted further!")
+        }
+        ChatMemberStatus::Administrator if !user_member.kind.can_be_edited() => {
+            Some("I can't change the rights of an admin who was promoted by someone else.")
+        }
+        ChatMemberStatus::Banned | ChatMemberStatus::Left => Some("This user isn't in the chat!"),
+        _ => None,
+    };
+    if let Some(refusal) = refusal {
+        bot.send_message(message.chat.id, refusal)
+            .reply_to_message_id(message.id)
+            .await?;
+        return Ok(());
+    }
+
+    // nobody can hand out rights they don't have themselves, and neither can the bot
+    let (bot_member, promoter_member) = tokio::try_join!(
+        admincache::get_admin(bot, chat.id, *BOT_ID),
+        admincache::get_admin(bot, chat.id, promoter_id)
+    )?;
+    let wanted_rights = preset_rights(&preset);
+    let mut granted_rights = [false; 8];
+    for (i, granted) in granted_rights.iter_mut().enumerate() {
+        *granted = wanted_rights[i]
+            && [&bot_member, &promoter_member].iter().all(|member| {
+                member
+                    .as_ref()
+                    .map_or(false, |member| RIGHT_CHECKS[i](&member.kind))
+            })
+            // basic groups don't have a separate right to pin messages
+            && (i != PIN_RIGHT || chat.is_supergroup());
+    }
+    let granted = preset_from_rights(chat.id, preset.name.clone(), granted_rights);
 
-    if user_member.kind.can_be_edited() {
-        bot.promote_chat_member(chat.id, user_id.unwrap())
-            .can_manage_chat(bot_chat_member.kind.can_manage_chat())
-            .can_change_info(bot_chat_member.kind.can_change_info())
-            .can_delete_messages(bot_chat_member.kind.can_delete_messages())
-            .can_manage_voice_chats(bot_chat_member.kind.can_manage_voice_chats())
-            .can_invite_users(bot_chat_member.kind.can_invite_users())
-            .can_restrict_members(bot_chat_member.kind.can_restrict_members())
-            .can_pin_messages(bot_chat_member.kind.can_pin_messages() && chat.is_supergroup())
-            .can_promote_members(bot_chat_member.kind.can_promote_members())
+    if !granted_rights.iter().any(|&granted| granted) {
+        bot.send_message(
+            message.chat.id,
+            format!(
+                "The {} preset has no rights that both you and I can hand out.",
+                html::escape(&preset.name)
+            ),
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(());
+    }
+
+    bot.promote_chat_member(chat.id, user_id)
+        .can_manage_chat(granted.can_manage_chat)
+        .can_change_info(granted.can_change_info)
+        .can_delete_messages(granted.can_delete_messages)
+        .can_manage_voice_chats(granted.can_manage_voice_chats)
+        .can_invite_users(granted.can_invite_users)
+        .can_restrict_members(granted.can_restrict_members)
+        .can_pin_messages(granted.can_pin_messages)
+        .can_promote_members(granted.can_promote_members)
+        .await?;
+    admincache::invalidate(chat.id);
+
+    let mut text = format!(
+        "Successfully promoted as {}!",
+        html::bold(&html::escape(&preset.name))
+    );
+
+    let mut missing = [false; 8];
+    for (i, missing) in missing.iter_mut().enumerate() {
+        *missing = wanted_rights[i] && !granted_rights[i];
+    }
+    if missing.iter().any(|&missing| missing) {
+        text.push_str(&format!(
+            "\nLeft out rights you or I don't have: {}",
+            describe_rights(&missing)
+        ));
+    }
+
+    if !title.is_empty() {
+        if let Err(err) = bot
+            .set_chat_administrator_custom_title(chat.id, user_id, title)
+            .await
+        {
+            text.push_str(&format!(
+                "\nI couldn't set their title: {}",
+                html::escape(&err.to_string())
+            ));
+        }
+    }
+
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn title(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let chat = &message.chat;
+
+    // check for required conditions
+    tokio::try_join!(
+        perms::require_group(bot, message), // command needs to be in a public group
+        perms::require_promote_chat_members(bot, message), // user requires CAN_PROMOTE_MEMBERS permissions
+        perms::require_bot_promote_chat_members(bot, message) // bot requires CAN_PROMOTE_MEMBERS permissions
+    )?;
+
+    // extract user ID and title from message
+    let (user_id, title) = utils::extract_user_and_text(bot, message, pool).await;
+    let user_id = match user_id {
+        Some(id) => id,
+        None => {
+            bot.send_message(message.chat.id, "Try targeting a user next time bud.")
+                .reply_to_message_id(message.id)
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let title = title.unwrap_or_default();
+    let title = title.trim();
+    if title.is_empty() {
+        bot.send_message(
+            message.chat.id,
+            "Usage: <code>/title @user &lt;title&gt;</code>",
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(());
+    }
+
+    if title.chars().count() > MAX_TITLE_LENGTH {
+        bot.send_message(
+            message.chat.id,
+            format!(
+                "Admin titles can be {} characters long at most.",
+                MAX_TITLE_LENGTH
+            ),
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(());
+    }
+
+    // check if user is valid
+    let user_member: ChatMember = match bot.get_chat_member(chat.id, user_id).await {
+        Ok(user) => user,
+        Err(_) => {
+            bot.send_message(message.chat.id, "This user is ded mate.")
+                .reply_to_message_id(message.id)
+                .await?; // invalid user (outdated info in db?)
+            return Ok(());
+        }
+    };
+
+    // telegram only lets bots set titles for admins they promoted
+    let refusal = match user_member.status() {
+        ChatMemberStatus::Administrator if user_member.kind.can_be_edited() => None,
+        ChatMemberStatus::Administrator => {
+            Some("I can only set titles for admins I promoted myself.")
+        }
+        ChatMemberStatus::Owner => Some("Only the chat owner can change their own title."),
+        _ => Some("Only admins can have a title, /promote them first."),
+    };
+    if let Some(refusal) = refusal {
+        bot.send_message(message.chat.id, refusal)
+            .reply_to_message_id(message.id)
             .await?;
+        return Ok(());
     }
 
-    bot.send_message(message.chat.id, "Successfully promoted!")
+    bot.set_chat_administrator_custom_title(chat.id, user_id, title)
+        .await?;
+    admincache::invalidate(chat.id);
+
+    bot.send_message(
+        message.chat.id,
+        format!("Their title is now {}.", html::bold(&html::escape(title))),
+    )
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(())
+}
+
+// list, define or remove the presets /promote can use
+pub async fn promote_presets(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    perms::require_group_or_connection(bot, message).await?;
+
+    let chat_id = utils::target_chat(message, pool).await?;
+    let args: Vec<_> = message
+        .text()
+        .map(|text| text.split_whitespace().skip(1).collect())
+        .unwrap_or_default();
+
+    let text = match args[..] {
+        [] => {
+            let presets = promotepresets::get_presets(chat_id, pool).await?;
+            let list: Vec<_> = presets
+                .iter()
+                .map(|preset| {
+                    format!(
+                        "- {}: {}",
+                        html::bold(&html::escape(&preset.name)),
+                        describe_rights(&preset_rights(preset))
+                    )
+                })
+                .collect();
+            format!("Promote presets in this chat:\n{}", list.join("\n"))
+        }
+        [name, "off"] => {
+            perms::require_promote_chat_members(bot, message).await?;
+
+            let name = name.to_lowercase();
+            match promotepresets::remove_preset(chat_id, &name, pool).await? {
+                true => format!("Removed the {} preset.", html::escape(&name)),
+                false => format!("This chat has no saved {} preset.", html::escape(&name)),
+            }
+        }
+        [name, ref rights @ ..] if !rights.is_empty() => {
+            perms::require_promote_chat_members(bot, message).await?;
+
+            let name = name.to_lowercase();
+            if !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                bot.send_message(
+                    message.chat.id,
+                    "Preset names can only contain letters, numbers and underscores.",
+                )
+                .reply_to_message_id(message.id)
+                .await?;
+                return Ok(());
+            }
+
+            let mut granted = [false; 8];
+            for right in rights {
+                let right = right.to_lowercase();
+                if right == "all" {
+                    granted = [true; 8];
+                    continue;
+                }
+                match RIGHT_NAMES.iter().position(|name| *name == right) {
+                    Some(index) => granted[index] = true,
+                    None => {
+                        bot.send_message(
+                            message.chat.id,
+                            format!(
+                                "I don't know the {} right. Presets can use: {}, or all",
+                                html::escape(&right),
+                                RIGHT_NAMES.join(", ")
+                            ),
+                        )
+                        .reply_to_message_id(message.id)
+                        .await?;
+                        return Ok(());
+                    }
+                }
+            }
+
+            promotepresets::set_preset(&preset_from_rights(chat_id, name.clone(), granted), pool)
+                .await?;
+
+            format!(
+                "Saved the {} preset: {}",
+                html::bold(&html::escape(&name)),
+                describe_rights(&granted)
+            )
+        }
+        _ => format!(
+            "Usage: <code>/presets</code>, <code>/presets &lt;name&gt; &lt;rights&gt;</code> or <code>/presets &lt;name&gt; off</code>\nRights: {}, or all",
+            RIGHT_NAMES.join(", ")
+        ),
+    };
+
+    bot.send_message(message.chat.id, text)
         .reply_to_message_id(message.id)
         .await?;
 
@@ -146,6 +516,7 @@ pub async fn demote(
             .can_pin_messages(false)
             .can_promote_members(false)
             .await?;
+        admincache::invalidate(chat.id);
     } else {
         bot.send_message(message.chat.id, "Could not demote. I might not be admin, or the admin status was appointed by another user, so I can't act upon them!")
 				.reply_to_message_id(message.id)
@@ -160,6 +531,43 @@ pub async fn demote(
     Ok(())
 }
 
+pub async fn admin_cache(bot: &crate::Bot, message: &Message) -> anyhow::Result<()> {
+    perms::require_group(bot, message).await?;
+
+    let user_id = match message.from() {
+        Some(user) => user.id,
+        None => return Ok(()),
+    };
+
+    // only admins get to force a refetch; anyone just promoted isn't in the cached list yet,
+    // so they're looked up on their own instead
+    let is_admin = perms::is_chat_admin(bot, message.chat.id, user_id).await?
+        || matches!(
+            bot.get_chat_member(message.chat.id, user_id)
+                .await
+                .map(|member| member.status()),
+            Ok(ChatMemberStatus::Administrator | ChatMemberStatus::Owner)
+        );
+    if !is_admin {
+        bot.send_message(message.chat.id, "You need to be an admin for this to work!")
+            .reply_to_message_id(message.id)
+            .await?;
+        return Ok(());
+    }
+
+    admincache::invalidate(message.chat.id);
+
+    let admins = admincache::get_admins(bot, message.chat.id).await?;
+    bot.send_message(
+        message.chat.id,
+        format!("Admin list refreshed, found {} admin(s).", admins.len()),
+    )
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(())
+}
+
 pub async fn pin(bot: &crate::Bot, message: &Message, mode: PinMode) -> anyhow::Result<()> {
     // check for required conditions
     tokio::try_join!(
@@ -184,8 +592,8 @@ pub async fn pin(bot: &crate::Bot, message: &Message, mode: PinMode) -> anyhow::
 pub async fn invite(bot: &crate::Bot, message: &Message) -> anyhow::Result<()> {
     match &message.chat.kind {
         teloxide::types::ChatKind::Public(c) => {
-            if c.invite_link.is_some() {
-                bot.send_message(message.chat.id, c.invite_link.as_ref().unwrap())
+            if let Some(invite_link) = &c.invite_link {
+                bot.send_message(message.chat.id, invite_link)
                     .reply_to_message_id(message.id)
                     .await?;
             } else {
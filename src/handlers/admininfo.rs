@@ -0,0 +1,298 @@
+use sqlx::{Pool, Postgres};
+use teloxide::{
+    payloads::SendMessageSetters,
+    prelude2::*,
+    types::{ChatMember, ChatMemberKind, ChatMemberStatus, ChatPermissions, UntilDate, User},
+    utils::html,
+};
+
+use crate::{
+    utils::{self, admincache, perms},
+    BOT_ID,
+};
+
+const RESTRICT: &str = "CAN_RESTRICT_MEMBERS";
+const DELETE: &str = "CAN_DELETE_MESSAGES";
+const PIN: &str = "CAN_PIN_MESSAGES";
+const PROMOTE: &str = "CAN_PROMOTE_MEMBERS";
+const INVITE: &str = "CAN_INVITE_USERS";
+
+// rights the bot needs for each module to do its job
+const MODULE_RIGHTS: [(&str, &[&str]); 13] = [
+    ("Bans, mutes and kicks", &[RESTRICT]),
+    ("Purges", &[DELETE]),
+    ("Pins", &[PIN]),
+    ("Promotions and titles", &[PROMOTE]),
+    ("Invite links", &[INVITE]),
+    ("Antiraid", &[RESTRICT]),
+    ("Antichannel", &[RESTRICT, DELETE]),
+    ("Antidupe", &[RESTRICT, DELETE]),
+    ("Link, media and script filters", &[RESTRICT, DELETE]),
+    ("Name filter", &[RESTRICT]),
+    ("Night mode", &[RESTRICT]),
+    ("Inactive pruning", &[RESTRICT]),
+    ("Appeals", &[RESTRICT]),
+];
+
+// name a user without mentioning them, so listing admins doesn't ping everyone
+fn user_name(user: &User) -> String {
+    match &user.username {
+        Some(username) => format!("{} (@{})", html::escape(&user.full_name()), username),
+        None => html::escape(&user.full_name()),
+    }
+}
+
+fn custom_title(member: &ChatMember) -> Option<&str> {
+    match &member.kind {
+        ChatMemberKind::Owner(owner) => owner.custom_title.as_deref(),
+        ChatMemberKind::Administrator(adm) => adm.custom_title.as_deref(),
+        _ => None,
+    }
+}
+
+fn yes_no(value: bool) -> String {
+    if value { "yes" } else { "no" }.to_owned()
+}
+
+fn until(until_date: &UntilDate) -> String {
+    match until_date {
+        UntilDate::Date(date) => format!("{} UTC", date.format("%Y-%m-%d %H:%M")),
+        UntilDate::Forever => "forever".to_owned(),
+    }
+}
+
+pub async fn admin_list(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    perms::require_group_or_connection(bot, message).await?;
+
+    let chat_id = utils::target_chat(message, pool).await?;
+    let admins = admincache::get_admins(bot, chat_id).await?;
+
+    let describe = |admin: &ChatMember| {
+        let mut line = user_name(&admin.user);
+        if let Some(title) = custom_title(admin) {
+            line.push_str(&format!(" - {}", html::italic(&html::escape(title))));
+        }
+        if admin.user.is_bot {
+            line.push_str(" [bot]");
+        }
+        line
+    };
+
+    let owner = admins
+        .iter()
+        .find(|admin| admin.status() == ChatMemberStatus::Owner)
+        .map(describe)
+        .unwrap_or_else(|| "unknown".to_owned());
+    let others: Vec<_> = admins
+        .iter()
+        .filter(|admin| admin.status() == ChatMemberStatus::Administrator)
+        .map(|admin| format!("- {}", describe(admin)))
+        .collect();
+
+    bot.send_message(
+        message.chat.id,
+        format!(
+            "Owner: {}\nAdmins ({}):\n{}",
+            owner,
+            others.len(),
+            match others.is_empty() {
+                true => "none".to_owned(),
+                false => others.join("\n"),
+            }
+        ),
+    )
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn user_perms(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    perms::require_group_or_connection(bot, message).await?;
+
+    let chat_id = utils::target_chat(message, pool).await?;
+
+    // extract user from message
+    let (user_id, _) = utils::extract_user_and_text(bot, message, pool).await;
+    let user_id = match user_id {
+        Some(id) => id,
+        None => {
+            bot.send_message(message.chat.id, "Try targeting a user next time bud.")
+                .reply_to_message_id(message.id)
+                .await?;
+            return Ok(());
+        }
+    };
+
+    // check if user is valid
+    let member = match bot.get_chat_member(chat_id, user_id).await {
+        Ok(member) => member,
+        Err(_) => {
+            bot.send_message(message.chat.id, "This user is ded mate.")
+                .reply_to_message_id(message.id)
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let mut rows = vec![];
+    match &member.kind {
+        ChatMemberKind::Owner(owner) => {
+            rows.push(("status", "owner".to_owned()));
+            rows.push(("anonymous", yes_no(owner.is_anonymous)));
+            rows.push(("rights", "all".to_owned()));
+        }
+        ChatMemberKind::Administrator(adm) => {
+            rows.push(("status", "admin".to_owned()));
+            rows.push(("anonymous", yes_no(adm.is_anonymous)));
+            rows.push(("editable by me", yes_no(adm.can_be_edited)));
+            rows.push(("manage chat", yes_no(adm.can_manage_chat)));
+            rows.push(("change info", yes_no(adm.can_change_info)));
+            rows.push(("delete messages", yes_no(adm.can_delete_messages)));
+            rows.push(("manage voice chats", yes_no(adm.can_manage_voice_chats)));
+            rows.push(("invite users", yes_no(adm.can_invite_users)));
+            rows.push(("restrict members", yes_no(adm.can_restrict_members)));
+            rows.push((
+                "pin messages",
+                yes_no(adm.can_pin_messages.unwrap_or(false)),
+            ));
+            rows.push(("promote members", yes_no(adm.can_promote_members)));
+        }
+        ChatMemberKind::Restricted(restricted) => {
+            rows.push(("status", "restricted".to_owned()));
+            rows.push(("until", until(&restricted.until_date)));
+            rows.push(("send messages", yes_no(restricted.can_send_messages)));
+            rows.push(("send media", yes_no(restricted.can_send_media_messages)));
+            rows.push((
+                "send stickers, gifs",
+                yes_no(restricted.can_send_other_messages),
+            ));
+            rows.push((
+                "link previews",
+                yes_no(restricted.can_add_web_page_previews),
+            ));
+        }
+        ChatMemberKind::Member => {
+            // members get whatever the chat allows by default
+            let defaults = bot
+                .get_chat(chat_id)
+                .await?
+                .permissions()
+                .unwrap_or_else(ChatPermissions::all);
+            let allows = |permission| yes_no(defaults.contains(permission));
+
+            rows.push(("status", "member".to_owned()));
+            rows.push(("send messages", allows(ChatPermissions::SEND_MESSAGES)));
+            rows.push(("send media", allows(ChatPermissions::SEND_MEDIA_MESSAGES)));
+            rows.push((
+                "send stickers, gifs",
+                allows(ChatPermissions::SEND_OTHER_MESSAGES),
+            ));
+            rows.push((
+                "link previews",
+                allows(ChatPermissions::ADD_WEB_PAGE_PREVIEWS),
+            ));
+            rows.push(("send polls", allows(ChatPermissions::SEND_POLLS)));
+            rows.push(("invite users", allows(ChatPermissions::INVITE_USERS)));
+            rows.push(("pin messages", allows(ChatPermissions::PIN_MESSAGES)));
+            rows.push(("change info", allows(ChatPermissions::CHANGE_INFO)));
+        }
+        ChatMemberKind::Left => rows.push(("status", "not in the chat".to_owned())),
+        ChatMemberKind::Banned(banned) => {
+            rows.push(("status", "banned".to_owned()));
+            rows.push(("until", until(&banned.until_date)));
+        }
+    }
+    if let Some(title) = custom_title(&member) {
+        rows.push(("title", title.to_owned()));
+    }
+    if member.user.is_bot {
+        rows.push(("bot", yes_no(true)));
+    }
+
+    let width = rows.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    let table: Vec<_> = rows
+        .iter()
+        .map(|(name, value)| format!("{:width$}  {}", name, value, width = width))
+        .collect();
+
+    bot.send_message(
+        message.chat.id,
+        format!(
+            "Permissions of {}:\n{}",
+            user_name(&member.user),
+            html::code_block(&table.join("\n"))
+        ),
+    )
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn bot_perms(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    perms::require_group_or_connection(bot, message).await?;
+
+    let chat_id = utils::target_chat(message, pool).await?;
+
+    if !perms::is_chat_admin(bot, chat_id, *BOT_ID).await? {
+        bot.send_message(
+            message.chat.id,
+            "I'm not an admin here, so none of the moderation modules can work. Promote me first!",
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(());
+    }
+
+    let (restrict, delete, pin, promote, invite) = tokio::try_join!(
+        perms::can_user_restrict_members(bot, chat_id, *BOT_ID),
+        perms::can_user_delete_messages(bot, chat_id, *BOT_ID),
+        perms::has_admin_right(bot, chat_id, *BOT_ID, ChatMemberKind::can_pin_messages),
+        perms::has_admin_right(bot, chat_id, *BOT_ID, ChatMemberKind::can_promote_members),
+        perms::has_admin_right(bot, chat_id, *BOT_ID, ChatMemberKind::can_invite_users)
+    )?;
+    let rights = [
+        (RESTRICT, restrict),
+        (DELETE, delete),
+        (PIN, pin),
+        (PROMOTE, promote),
+        (INVITE, invite),
+    ];
+
+    let lines: Vec<_> = MODULE_RIGHTS
+        .iter()
+        .map(|(module, needed)| {
+            let missing: Vec<_> = needed
+                .iter()
+                .filter(|right| rights.iter().any(|(name, has)| name == *right && !has))
+                .copied()
+                .collect();
+            match missing.is_empty() {
+                true => format!("- {}: ok", module),
+                false => format!("- {}: missing {}", module, missing.join(", ")),
+            }
+        })
+        .collect();
+
+    bot.send_message(
+        message.chat.id,
+        format!("My rights for each module:\n{}", lines.join("\n")),
+    )
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(())
+}
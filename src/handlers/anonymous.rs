@@ -0,0 +1,118 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use chrono::{Duration, Utc};
+use lazy_static::lazy_static;
+use teloxide::{
+    payloads::SendMessageSetters,
+    prelude2::*,
+    types::{InlineKeyboardButton, InlineKeyboardMarkup, MessageKind},
+};
+
+use crate::{
+    handlers::disabling,
+    utils::{self, perms},
+};
+
+// how long an anonymous command waits for an admin to claim it
+const VERIFY_TIMEOUT_MINUTES: i64 = 10;
+
+lazy_static! {
+    // commands from anonymous admins waiting for verification, by chat and message ID
+    static ref PENDING_COMMANDS: Mutex<HashMap<(i64, i32), Message>> = Mutex::new(HashMap::new());
+}
+
+// ask an anonymous admin to reveal themselves before their command runs, returning true if asked
+pub async fn request_verification(bot: &crate::Bot, message: &Message) -> anyhow::Result<bool> {
+    if message.chat.is_private() || !utils::is_anonymous_admin(message) {
+        return Ok(false);
+    }
+
+    // commands anyone can use don't need to know who sent them
+    match utils::message_command(message) {
+        Some(command) if !disabling::DISABLEABLE_COMMANDS.contains(&command.as_str()) => {}
+        _ => return Ok(false),
+    }
+
+    {
+        let mut pending = PENDING_COMMANDS.lock().unwrap();
+        let expired = Utc::now() - Duration::minutes(VERIFY_TIMEOUT_MINUTES);
+        pending.retain(|_, pending| pending.date > expired);
+        pending.insert((message.chat.id, message.id), message.clone());
+    }
+
+    let keyboard =
+        InlineKeyboardMarkup::default().append_row(vec![InlineKeyboardButton::callback(
+            "Press to prove admin".to_owned(),
+            format!("anonadmin:{}", message.id),
+        )]);
+
+    bot.send_message(
+        message.chat.id,
+        "You're posting anonymously, so I can't tell whether you're allowed to do this. Press the button to prove you're an admin.",
+    )
+    .reply_markup(keyboard)
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(true)
+}
+
+// verify the admin pressing the button, returning their command as if they had sent it themselves
+pub async fn handle_callback(
+    bot: &crate::Bot,
+    query: &CallbackQuery,
+) -> anyhow::Result<Option<Message>> {
+    let prompt = match &query.message {
+        Some(message) => message,
+        None => return Ok(None),
+    };
+    let chat_id = prompt.chat.id;
+
+    let message_id = match query
+        .data
+        .as_deref()
+        .and_then(|data| data.split(':').nth(1))
+        .and_then(|id| id.parse::<i32>().ok())
+    {
+        Some(message_id) => message_id,
+        None => {
+            bot.answer_callback_query(query.id.clone()).await?;
+            return Ok(None);
+        }
+    };
+
+    // the command's own checks still apply once it runs, this only keeps non-admins out
+    if !perms::is_chat_admin(bot, chat_id, query.from.id).await? {
+        bot.answer_callback_query(query.id.clone())
+            .text("You need to be an admin of this chat for this.")
+            .show_alert(true)
+            .await?;
+        return Ok(None);
+    }
+
+    let message = PENDING_COMMANDS
+        .lock()
+        .unwrap()
+        .remove(&(chat_id, message_id));
+    let mut message = match message {
+        Some(message) => message,
+        None => {
+            bot.answer_callback_query(query.id.clone())
+                .text("This command expired, send it again.")
+                .show_alert(true)
+                .await?;
+            bot.delete_message(chat_id, prompt.id).await.ok();
+            return Ok(None);
+        }
+    };
+
+    if let MessageKind::Common(common) = &mut message.kind {
+        common.from = Some(query.from.clone());
+        common.sender_chat = None;
+    }
+
+    bot.answer_callback_query(query.id.clone()).await?;
+    bot.delete_message(chat_id, prompt.id).await.ok();
+
+    Ok(Some(message))
+}
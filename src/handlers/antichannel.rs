@@ -0,0 +1,99 @@
+use sqlx::{Pool, Postgres};
+use teloxide::{payloads::SendMessageSetters, prelude2::*};
+
+use crate::{
+    handlers::banning,
+    repo::antichannel,
+    utils::{self, perms},
+};
+
+// delete and ban messages sent on behalf of a channel, returning true if the message was removed
+pub async fn handle_message(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<bool> {
+    let sender_chat = match message.sender_chat() {
+        Some(sender_chat) => sender_chat,
+        None => return Ok(false),
+    };
+
+    // anonymous admins post as the group itself, and linked channel posts are forwarded automatically
+    if message.chat.is_private()
+        || sender_chat.id == message.chat.id
+        || message.is_automatic_forward()
+    {
+        return Ok(false);
+    }
+
+    if !antichannel::get_antichannel(message.chat.id, pool).await? {
+        return Ok(false);
+    }
+
+    // the linked channel is allowed to comment in its own discussion group
+    let chat = bot.get_chat(message.chat.id).await?;
+    if chat.linked_chat_id() == Some(sender_chat.id) {
+        return Ok(false);
+    }
+
+    log::info!(
+        "Removing message from channel {} in chat {}",
+        sender_chat.id,
+        message.chat.id
+    );
+    bot.delete_message(message.chat.id, message.id).await.ok();
+    banning::ban_user(bot, message.chat.id, sender_chat.id, None).await?;
+
+    Ok(true)
+}
+
+pub async fn antichannel(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    // check for required conditions
+    tokio::try_join!(
+        perms::require_group_or_connection(bot, message), // needs a group or a connected PM
+        perms::require_restrict_chat_members(bot, message), // user requires RESTRICT_CHAT_MEMBERS permissions
+        perms::require_bot_restrict_chat_members(bot, message), // bot requires RESTRICT_CHAT_MEMBERS permissions
+        perms::require_bot_can_delete_messages(bot, message), // bot requires CAN_DELETE_MESSAGES permissions
+    )?;
+
+    let chat_id = utils::target_chat(message, pool).await?;
+
+    match message
+        .text()
+        .and_then(|text| text.split_whitespace().nth(1))
+    {
+        Some("on") | Some("yes") => {
+            antichannel::set_antichannel(chat_id, true, pool).await?;
+            bot.send_message(
+                message.chat.id,
+                "Anti-channel mode is on. Messages sent as a channel will be deleted and the channel banned.",
+            )
+            .reply_to_message_id(message.id)
+            .await?;
+        }
+        Some("off") | Some("no") => {
+            antichannel::set_antichannel(chat_id, false, pool).await?;
+            bot.send_message(message.chat.id, "Anti-channel mode has been turned off.")
+                .reply_to_message_id(message.id)
+                .await?;
+        }
+        _ => {
+            let enabled = antichannel::get_antichannel(chat_id, pool).await?;
+            bot.send_message(
+                message.chat.id,
+                format!(
+                    "Anti-channel mode is currently {} in this chat. Use <code>/antichannel on</code> or <code>/antichannel off</code> to change it.",
+                    if enabled { "on" } else { "off" }
+                ),
+            )
+            .reply_to_message_id(message.id)
+            .await?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,407 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, HashSet, VecDeque},
+    hash::{Hash, Hasher},
+    sync::Mutex,
+};
+
+use chrono::{DateTime, Duration, Utc};
+use lazy_static::lazy_static;
+use sqlx::{Pool, Postgres};
+use teloxide::{payloads::SendMessageSetters, prelude2::*};
+
+use crate::{
+    handlers::muting,
+    repo::antidupe,
+    utils::{self, perms, TimeSpan},
+};
+
+// characters that don't render but can be used to make copies look different
+const ZERO_WIDTH_CHARS: [char; 7] = [
+    '\u{00AD}', '\u{180E}', '\u{200B}', '\u{200C}', '\u{200D}', '\u{2060}', '\u{FEFF}',
+];
+
+// short messages like 'ok' or 'thanks' are repeated all the time, so they're never duplicates
+const MIN_TEXT_LENGTH: usize = 8;
+
+// longest window messages are compared in, to keep memory use in check
+const MAX_WINDOW: i64 = 60 * 60;
+
+// number of recent messages remembered per chat
+const MAX_TRACKED_MESSAGES: usize = 500;
+
+struct RecentMessage {
+    sent_at: DateTime<Utc>,
+    hash: u64,
+    sender_id: i64,
+    message_id: i32,
+    // whether this message was already punished as a duplicate
+    flagged: bool,
+}
+
+lazy_static! {
+    // recent message hashes per chat, used to detect duplicates
+    static ref RECENT_MESSAGES: Mutex<HashMap<i64, VecDeque<RecentMessage>>> =
+        Mutex::new(HashMap::new());
+}
+
+// hash text so that copies differing only in case, spacing or invisible characters match
+fn normalized_hash(text: &str) -> Option<u64> {
+    let normalized: String = text
+        .chars()
+        .filter(|c| !c.is_whitespace() && !ZERO_WIDTH_CHARS.contains(c))
+        .flat_map(char::to_lowercase)
+        .collect();
+    if normalized.chars().count() < MIN_TEXT_LENGTH {
+        return None;
+    }
+
+    let mut hasher = DefaultHasher::new();
+    normalized.hash(&mut hasher);
+    Some(hasher.finish())
+}
+
+// record a message and return the (sender, message) pairs that should be punished as duplicates
+fn find_duplicates(
+    chat_id: i64,
+    message: RecentMessage,
+    window: Duration,
+    repeat_threshold: i32,
+    user_threshold: i32,
+) -> Vec<(i64, i32)> {
+    let mut recent_messages = RECENT_MESSAGES.lock().unwrap();
+    let chat_messages = recent_messages.entry(chat_id).or_default();
+
+    // forget messages that fell out of the window
+    while let Some(first) = chat_messages.front() {
+        if message.sent_at.signed_duration_since(first.sent_at) > window
+            || chat_messages.len() >= MAX_TRACKED_MESSAGES
+        {
+            chat_messages.pop_front();
+        } else {
+            break;
+        }
+    }
+
+    let (hash, sender_id) = (message.hash, message.sender_id);
+    chat_messages.push_back(message);
+
+    let copies: Vec<_> = chat_messages
+        .iter_mut()
+        .filter(|recent| recent.hash == hash)
+        .collect();
+
+    // content that was already flagged gets removed on sight
+    if copies.iter().any(|copy| copy.flagged) {
+        return copies
+            .into_iter()
+            .filter(|copy| !copy.flagged)
+            .map(|copy| {
+                copy.flagged = true;
+                (copy.sender_id, copy.message_id)
+            })
+            .collect();
+    }
+
+    let repeats = copies
+        .iter()
+        .filter(|copy| copy.sender_id == sender_id)
+        .count();
+    let users = copies
+        .iter()
+        .map(|copy| copy.sender_id)
+        .collect::<HashSet<_>>()
+        .len();
+    let is_duplicate = (repeat_threshold > 0 && repeats >= repeat_threshold as usize)
+        || (user_threshold > 0 && users >= user_threshold as usize);
+    if !is_duplicate {
+        return vec![];
+    }
+
+    copies
+        .into_iter()
+        .map(|copy| {
+            copy.flagged = true;
+            (copy.sender_id, copy.message_id)
+        })
+        .collect()
+}
+
+// delete and mute duplicated messages, returning true if the message was removed
+pub async fn handle_message(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<bool> {
+    if message.chat.is_private() {
+        return Ok(false);
+    }
+
+    let hash = match message
+        .text()
+        .or_else(|| message.caption())
+        .and_then(normalized_hash)
+    {
+        Some(hash) => hash,
+        None => return Ok(false),
+    };
+
+    let settings = antidupe::get_antidupe(message.chat.id, pool).await?;
+    if settings.repeat_threshold == 0 && settings.user_threshold == 0 {
+        return Ok(false);
+    }
+
+    // admins and approved users can repeat themselves, and don't count towards duplicates
+    let sender_id = match utils::sender_id(message) {
+        Some(sender_id) => sender_id,
+        None => return Ok(false),
+    };
+    if perms::is_user_exempt(bot, message.chat.id, sender_id, pool).await? {
+        return Ok(false);
+    }
+
+    let duplicates = find_duplicates(
+        message.chat.id,
+        RecentMessage {
+            sent_at: message.date,
+            hash,
+            sender_id,
+            message_id: message.id,
+            flagged: false,
+        },
+        Duration::seconds(settings.window_duration),
+        settings.repeat_threshold,
+        settings.user_threshold,
+    );
+    if duplicates.is_empty() {
+        return Ok(false);
+    }
+
+    log::info!(
+        "Removing {} duplicate message(s) in chat {}",
+        duplicates.len(),
+        message.chat.id
+    );
+
+    let mute_duration = TimeSpan::from_seconds(settings.mute_duration);
+    let until = mute_duration.until(message.date);
+    let mut muted = HashSet::new();
+    for (sender_id, message_id) in duplicates {
+        bot.delete_message(message.chat.id, message_id).await.ok();
+
+        // channels can't be muted, deleting their posts has to do
+        if utils::is_channel(sender_id) || !muted.insert(sender_id) {
+            continue;
+        }
+        if let Err(err) = muting::mute_user(bot, message.chat.id, sender_id, Some(until)).await {
+            log::error!(
+                "Failed to mute {} in {} for duplicates: {}",
+                sender_id,
+                message.chat.id,
+                err
+            );
+            muted.remove(&sender_id);
+        }
+    }
+
+    let text = match muted.len() {
+        0 => return Ok(true),
+        1 if muted.contains(&sender_id) => format!(
+            "{} was muted for {} for posting duplicate messages.",
+            utils::sender_mention(message),
+            mute_duration
+        ),
+        n => format!(
+            "Muted {} user(s) for {} for posting duplicate messages.",
+            n, mute_duration
+        ),
+    };
+    bot.send_message(message.chat.id, text).await?;
+
+    Ok(true)
+}
+
+pub async fn antidupe(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    // check for required conditions
+    tokio::try_join!(
+        perms::require_group_or_connection(bot, message), // needs a group or a connected PM
+        perms::require_restrict_chat_members(bot, message), // user requires RESTRICT_CHAT_MEMBERS permissions
+        perms::require_bot_restrict_chat_members(bot, message), // bot requires RESTRICT_CHAT_MEMBERS permissions
+        perms::require_bot_can_delete_messages(bot, message), // bot requires CAN_DELETE_MESSAGES permissions
+    )?;
+
+    let chat_id = utils::target_chat(message, pool).await?;
+    let args: Vec<_> = message
+        .text()
+        .map(|text| text.split_whitespace().skip(1).collect())
+        .unwrap_or_default();
+    let mut settings = antidupe::get_antidupe(chat_id, pool).await?;
+
+    // only valid changes are saved, anything else just gets an explanation back
+    let changed = match args[..] {
+        [] => {
+            let describe = |threshold: i32, what: &str| match threshold {
+                0 => format!("{}: off", what),
+                n => format!("{}: {} copies", what, n),
+            };
+
+            bot.send_message(
+                message.chat.id,
+                format!(
+                    "Duplicate messages are compared within {} and their senders muted for {}.\n{}\n{}",
+                    TimeSpan::from_seconds(settings.window_duration),
+                    TimeSpan::from_seconds(settings.mute_duration),
+                    describe(settings.repeat_threshold, "Same user repeating"),
+                    describe(settings.user_threshold, "Different users posting"),
+                ),
+            )
+            .reply_to_message_id(message.id)
+            .await?;
+            return Ok(());
+        }
+        ["off"] => {
+            settings.repeat_threshold = 0;
+            settings.user_threshold = 0;
+            Ok("Duplicate message detection has been turned off.".to_owned())
+        }
+        [kind @ ("repeats" | "users"), threshold] => match threshold.parse::<i32>() {
+            Ok(threshold) if threshold == 0 || threshold >= 2 => {
+                let what = match kind {
+                    "repeats" => {
+                        settings.repeat_threshold = threshold;
+                        "the same user posts"
+                    }
+                    _ => {
+                        settings.user_threshold = threshold;
+                        "different users post"
+                    }
+                };
+                Ok(match threshold {
+                    0 => format!("Messages {} won't be checked for duplicates.", what),
+                    n => format!(
+                        "Messages will be removed once {} the same thing {} times.",
+                        what, n
+                    ),
+                })
+            }
+            _ => Err("The threshold needs to be at least 2, or 0 to disable it.".to_owned()),
+        },
+        [setting @ ("window" | "mutetime"), ..] => match args[1..].join(" ").parse::<TimeSpan>() {
+            Ok(span) if setting == "window" && span.seconds() > MAX_WINDOW => {
+                Err("The window can be an hour long at most.".to_owned())
+            }
+            Ok(span) if setting == "window" => {
+                settings.window_duration = span.seconds();
+                Ok(format!("Messages will be compared within {}.", span))
+            }
+            Ok(span) => {
+                settings.mute_duration = span.seconds();
+                Ok(format!("Senders of duplicate messages will be muted for {}.", span))
+            }
+            Err(err) => Err(err.to_owned()),
+        },
+        _ => Err("Usage: <code>/antidupe repeats &lt;copies&gt;</code>, <code>/antidupe users &lt;copies&gt;</code>, <code>/antidupe window &lt;duration&gt;</code>, <code>/antidupe mutetime &lt;duration&gt;</code> or <code>/antidupe off</code>".to_owned()),
+    };
+
+    let text = match changed {
+        Ok(text) => {
+            antidupe::set_antidupe(&settings, pool).await?;
+            text
+        }
+        Err(text) => text,
+    };
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    // each test uses its own chat, since the tracked messages are shared
+    fn send(
+        chat_id: i64,
+        secs: i64,
+        text: &str,
+        sender_id: i64,
+        message_id: i32,
+        repeat_threshold: i32,
+        user_threshold: i32,
+    ) -> Vec<(i64, i32)> {
+        let message = RecentMessage {
+            sent_at: Utc.ymd(2026, 10, 19).and_hms(12, 0, 0) + Duration::seconds(secs),
+            hash: normalized_hash(text).unwrap(),
+            sender_id,
+            message_id,
+            flagged: false,
+        };
+        let mut found = find_duplicates(
+            chat_id,
+            message,
+            Duration::seconds(60),
+            repeat_threshold,
+            user_threshold,
+        );
+        found.sort_unstable();
+        found
+    }
+
+    #[test]
+    fn hash_ignores_case_spacing_and_invisible_characters() {
+        assert_eq!(
+            normalized_hash("Buy cheap stuff here"),
+            normalized_hash("buy  CHEAP\u{200B} stuff\nhere")
+        );
+        assert_ne!(
+            normalized_hash("Buy cheap stuff here"),
+            normalized_hash("Buy cheap stuff there")
+        );
+        assert_eq!(normalized_hash("thanks!"), None);
+    }
+
+    #[test]
+    fn repeats_from_one_user() {
+        assert!(send(-1, 0, "spam spam spam", 1, 1, 3, 0).is_empty());
+        assert!(send(-1, 1, "spam spam spam", 1, 2, 3, 0).is_empty());
+        assert_eq!(
+            send(-1, 2, "spam spam spam", 1, 3, 3, 0),
+            vec![(1, 1), (1, 2), (1, 3)]
+        );
+        // later copies are removed on sight
+        assert_eq!(send(-1, 3, "spam spam spam", 1, 4, 3, 0), vec![(1, 4)]);
+    }
+
+    #[test]
+    fn copies_from_several_users() {
+        assert!(send(-2, 0, "join my channel now", 1, 1, 0, 2).is_empty());
+        assert!(send(-2, 1, "join my channel now", 1, 2, 0, 2).is_empty());
+        assert_eq!(
+            send(-2, 2, "join my channel now", 2, 3, 0, 2),
+            vec![(1, 1), (1, 2), (2, 3)]
+        );
+    }
+
+    #[test]
+    fn copies_outside_the_window() {
+        assert!(send(-3, 0, "spam spam spam", 1, 1, 2, 0).is_empty());
+        assert!(send(-3, 61, "spam spam spam", 1, 2, 2, 0).is_empty());
+        assert_eq!(
+            send(-3, 62, "spam spam spam", 1, 3, 2, 0),
+            vec![(1, 2), (1, 3)]
+        );
+    }
+
+    #[test]
+    fn disabled_thresholds() {
+        for id in 1..=5 {
+            assert!(send(-4, id as i64, "spam spam spam", id as i64, id, 0, 0).is_empty());
+        }
+    }
+}
@@ -0,0 +1,351 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+    time::Duration as StdDuration,
+};
+
+use chrono::{DateTime, Duration, Utc};
+use futures::{stream, StreamExt};
+use lazy_static::lazy_static;
+use sqlx::{Pool, Postgres};
+use teloxide::{
+    payloads::SendMessageSetters,
+    prelude2::*,
+    types::{InlineKeyboardButton, InlineKeyboardMarkup},
+};
+
+use crate::{
+    handlers::banning,
+    repo::{antiraid, appeals},
+    utils::{self, perms, TimeSpan},
+    BOT_ID,
+};
+
+// how often chats are checked for raids that have run their course
+const SCHEDULER_INTERVAL: StdDuration = StdDuration::from_secs(60);
+
+// number of unban requests allowed in flight at once
+const MAX_CONCURRENT_UNBANS: usize = 10;
+
+// join times with who joined, oldest first
+type JoinLog = VecDeque<(DateTime<Utc>, i64)>;
+
+lazy_static! {
+    // recent joins per chat, used to detect raids
+    static ref RECENT_JOINS: Mutex<HashMap<i64, JoinLog>> =
+        Mutex::new(HashMap::new());
+}
+
+// record joins and, once the chat crosses its joins-per-minute threshold, return everyone in the burst
+fn take_join_burst(
+    chat_id: i64,
+    joined_at: DateTime<Utc>,
+    user_ids: &[i64],
+    threshold: i32,
+) -> Option<Vec<i64>> {
+    let mut recent_joins = RECENT_JOINS.lock().unwrap();
+    let chat_joins = recent_joins.entry(chat_id).or_default();
+
+    for user_id in user_ids {
+        chat_joins.push_back((joined_at, *user_id));
+    }
+
+    // forget joins older than a minute
+    while let Some((first, _)) = chat_joins.front() {
+        if joined_at.signed_duration_since(*first) > Duration::minutes(1) {
+            chat_joins.pop_front();
+        } else {
+            break;
+        }
+    }
+
+    if chat_joins.len() >= threshold as usize {
+        return Some(chat_joins.drain(..).map(|(_, user_id)| user_id).collect());
+    }
+
+    None
+}
+
+async fn start_raid(
+    bot: &crate::Bot,
+    chat_id: i64,
+    until: DateTime<Utc>,
+    ban_duration: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    antiraid::set_raid_until(chat_id, Some(until), pool).await?;
+
+    bot.send_message(
+        chat_id,
+        format!(
+            "Anti-raid mode is on until {} UTC. Everyone who joins will be banned for {} minute(s).",
+            until.format("%Y-%m-%d %H:%M"),
+            ban_duration / 60
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+async fn end_raid(bot: &crate::Bot, chat_id: i64, pool: &Pool<Postgres>) -> anyhow::Result<()> {
+    antiraid::set_raid_until(chat_id, None, pool).await?;
+
+    let raiders = antiraid::count_raiders(chat_id, pool).await?;
+    if raiders == 0 {
+        bot.send_message(chat_id, "Anti-raid mode is off. Nobody was banned.")
+            .await?;
+        return Ok(());
+    }
+
+    let keyboard =
+        InlineKeyboardMarkup::default().append_row(vec![InlineKeyboardButton::callback(
+            "Unban all raiders".to_owned(),
+            "antiraid:unban".to_owned(),
+        )]);
+
+    bot.send_message(
+        chat_id,
+        format!(
+            "Anti-raid mode is off. {} user(s) banned as raiders haven't been unbanned yet.",
+            raiders
+        ),
+    )
+    .reply_markup(keyboard)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn handle_joins(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let members: Vec<_> = match message.new_chat_members() {
+        Some(members) => members
+            .iter()
+            .filter(|m| m.id != *BOT_ID)
+            .map(|m| m.id)
+            .collect(),
+        None => return Ok(()),
+    };
+    if members.is_empty() {
+        return Ok(());
+    }
+
+    let chat_id = message.chat.id;
+    let settings = antiraid::get_antiraid(chat_id, pool).await?;
+
+    let raiders = if matches!(settings.raid_until, Some(until) if until > message.date) {
+        members
+    } else if settings.threshold > 0 {
+        // switch into raid mode automatically when too many users join at once,
+        // everyone who joined in the burst counts as a raider
+        match take_join_burst(chat_id, message.date, &members, settings.threshold) {
+            Some(burst) => {
+                let until = message.date + Duration::seconds(settings.raid_duration);
+                start_raid(bot, chat_id, until, settings.ban_duration, pool).await?;
+                burst
+            }
+            None => return Ok(()),
+        }
+    } else {
+        return Ok(());
+    };
+
+    // temporarily ban everyone who joined during the raid
+    let until = message.date + Duration::seconds(settings.ban_duration);
+    let total = raiders.len();
+    let (summary, banned) = utils::mass_action(
+        raiders.into_iter().map(Ok).collect(),
+        "Banned",
+        |user_id| async move {
+            utils::retry_rate_limited(|| banning::ban_user(bot, chat_id, user_id, Some(until)))
+                .await
+                .map_err(|err| err.to_string())
+        },
+    )
+    .await;
+
+    for user_id in &banned {
+        antiraid::add_raider(chat_id, *user_id, pool).await?;
+    }
+    if banned.len() < total {
+        log::error!("Failed to ban some raiders in {}: {}", chat_id, summary);
+    }
+
+    Ok(())
+}
+
+pub async fn antiraid(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    // check for required conditions
+    tokio::try_join!(
+        perms::require_group_or_connection(bot, message), // needs a group or a connected PM
+        perms::require_restrict_chat_members(bot, message), // user requires RESTRICT_CHAT_MEMBERS permissions
+        perms::require_bot_restrict_chat_members(bot, message) // bot requires RESTRICT_CHAT_MEMBERS permissions
+    )?;
+
+    let chat_id = utils::target_chat(message, pool).await?;
+    let args: Vec<_> = message
+        .text()
+        .map(|text| text.split_whitespace().skip(1).collect())
+        .unwrap_or_default();
+
+    match args[..] {
+        [] => {
+            let settings = antiraid::get_antiraid(chat_id, pool).await?;
+            let status = match settings.raid_until {
+                Some(until) if until > message.date => {
+                    format!("on until {} UTC", until.format("%Y-%m-%d %H:%M"))
+                }
+                _ => "off".to_owned(),
+            };
+            let threshold = match settings.threshold {
+                0 => "Automatic raid detection is disabled.".to_owned(),
+                n => format!(
+                    "Raid mode turns on automatically when {} users join within a minute.",
+                    n
+                ),
+            };
+
+            bot.send_message(
+                message.chat.id,
+                format!(
+                    "Anti-raid mode is {}. Users joining during a raid are banned for {} minute(s).\n{}",
+                    status,
+                    settings.ban_duration / 60,
+                    threshold
+                ),
+            )
+            .reply_to_message_id(message.id)
+            .await?;
+        }
+        ["off"] => {
+            end_raid(bot, chat_id, pool).await?;
+        }
+        ["threshold", threshold] => match threshold.parse::<i32>() {
+            Ok(threshold) if threshold >= 0 => {
+                antiraid::set_threshold(chat_id, threshold, pool).await?;
+                bot.send_message(
+                    message.chat.id,
+                    match threshold {
+                        0 => "Automatic raid detection has been disabled.".to_owned(),
+                        n => format!(
+                            "Raid mode will turn on when {} users join within a minute.",
+                            n
+                        ),
+                    },
+                )
+                .reply_to_message_id(message.id)
+                .await?;
+            }
+            _ => {
+                bot.send_message(message.chat.id, "The threshold needs to be a positive number, or 0 to disable automatic detection.")
+                    .reply_to_message_id(message.id)
+                    .await?;
+            }
+        },
+        ["bantime", ..] => match args[1..].join(" ").parse::<TimeSpan>() {
+            Ok(span) => {
+                antiraid::set_ban_duration(chat_id, span.seconds(), pool).await?;
+                bot.send_message(
+                    message.chat.id,
+                    format!("Users joining during a raid will be banned for {}.", span),
+                )
+                .reply_to_message_id(message.id)
+                .await?;
+            }
+            Err(err) => {
+                bot.send_message(message.chat.id, err)
+                    .reply_to_message_id(message.id)
+                    .await?;
+            }
+        },
+        _ => match args.join(" ").parse::<TimeSpan>() {
+            Ok(span) => {
+                let settings = antiraid::get_antiraid(chat_id, pool).await?;
+                let until = span.until(message.date);
+                start_raid(bot, chat_id, until, settings.ban_duration, pool).await?;
+            }
+            Err(_) => {
+                bot.send_message(
+                    message.chat.id,
+                    "Usage: <code>/antiraid &lt;duration&gt;</code>, <code>/antiraid off</code>, <code>/antiraid threshold &lt;joins per minute&gt;</code> or <code>/antiraid bantime &lt;duration&gt;</code>",
+                )
+                .reply_to_message_id(message.id)
+                .await?;
+            }
+        },
+    }
+
+    Ok(())
+}
+
+async fn end_expired_raids(bot: &crate::Bot, pool: &Pool<Postgres>) -> anyhow::Result<()> {
+    for raid in antiraid::get_expired_raids(pool).await? {
+        if let Err(err) = end_raid(bot, raid.chat_id, pool).await {
+            log::error!("Failed to end raid in chat {}: {}", raid.chat_id, err);
+        }
+    }
+
+    Ok(())
+}
+
+// periodically turn off raid mode for chats whose raid has run its course
+pub async fn run_scheduler(bot: crate::Bot, pool: &Pool<Postgres>) {
+    let mut interval = tokio::time::interval(SCHEDULER_INTERVAL);
+
+    loop {
+        interval.tick().await;
+        if let Err(err) = end_expired_raids(&bot, pool).await {
+            log::error!("Failed to check raids: {}", err);
+        }
+    }
+}
+
+pub async fn handle_callback(
+    bot: &crate::Bot,
+    query: &CallbackQuery,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let summary_msg = match &query.message {
+        Some(message) => message,
+        None => return Ok(()),
+    };
+    let chat_id = summary_msg.chat.id;
+
+    // only people who could have banned them can unban them
+    if !perms::can_restrict_from_reply(bot, summary_msg, query.from.id).await? {
+        bot.answer_callback_query(query.id.clone())
+            .text("You're missing the required permission for this action: CAN_RESTRICT_MEMBERS.")
+            .show_alert(true)
+            .await?;
+        return Ok(());
+    }
+
+    let raiders = antiraid::take_raiders(chat_id, pool).await?;
+    let unbanned = stream::iter(raiders.iter().copied())
+        .map(|user_id| async move {
+            utils::retry_rate_limited(|| banning::unban_user(bot, chat_id, user_id)).await?;
+            appeals::withdraw_appeals(chat_id, user_id, pool).await
+        })
+        .buffer_unordered(MAX_CONCURRENT_UNBANS)
+        .filter(|res| futures::future::ready(res.is_ok()))
+        .count()
+        .await;
+
+    bot.answer_callback_query(query.id.clone()).await?;
+    bot.edit_message_text(
+        chat_id,
+        summary_msg.id,
+        format!("Unbanned {} of {} raider(s).", unbanned, raiders.len()),
+    )
+    .await?;
+
+    Ok(())
+}
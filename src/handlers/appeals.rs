@@ -0,0 +1,273 @@
+use futures::future::join_all;
+use sqlx::{Pool, Postgres};
+use teloxide::{
+    payloads::SendMessageSetters,
+    prelude2::*,
+    types::{ChatMemberStatus, InlineKeyboardButton, InlineKeyboardMarkup},
+    utils::html,
+};
+
+use crate::{
+    handlers::banning,
+    repo::{appeals, chats},
+    utils::{admincache, perms},
+};
+
+// number of times a user may appeal a ban in a single chat
+const MAX_APPEALS: i64 = 3;
+
+fn appeal_keyboard(appeal_id: i32) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::default().append_row(vec![
+        InlineKeyboardButton::callback("Unban".to_owned(), format!("appeal:unban:{}", appeal_id)),
+        InlineKeyboardButton::callback("Reject".to_owned(), format!("appeal:reject:{}", appeal_id)),
+    ])
+}
+
+pub async fn appeal(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    // appeals come from banned users, who can't talk in the chat anyway
+    if !message.chat.is_private() {
+        bot.send_message(
+            message.chat.id,
+            "Appeals are handled in private, message me directly!",
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(());
+    }
+
+    let user = match message.from() {
+        Some(user) => user,
+        None => return Ok(()),
+    };
+
+    // split into chat and optional reason
+    let args: Vec<_> = message
+        .text()
+        .map(|text| text.splitn(3, char::is_whitespace).skip(1).collect())
+        .unwrap_or_default();
+    let chat_arg = match args.first() {
+        Some(arg) if !arg.is_empty() => *arg,
+        _ => {
+            bot.send_message(
+                message.chat.id,
+                "Usage: <code>/appeal &lt;chat ID or @username&gt; [reason]</code>",
+            )
+            .reply_to_message_id(message.id)
+            .await?;
+            return Ok(());
+        }
+    };
+    let reason = args
+        .get(1)
+        .map(|reason| reason.trim().to_owned())
+        .filter(|reason| !reason.is_empty());
+
+    let chat = match chat_arg.parse::<i64>() {
+        Ok(chat_id) => bot.get_chat(chat_id).await,
+        Err(_) => bot.get_chat(chat_arg.to_owned()).await,
+    };
+    let chat = match chat {
+        Ok(chat) if !chat.is_private() => chat,
+        _ => {
+            bot.send_message(
+                message.chat.id,
+                "I couldn't find that chat; are you sure I'm in it?",
+            )
+            .reply_to_message_id(message.id)
+            .await?;
+            return Ok(());
+        }
+    };
+    let chat_title = chat.title().unwrap_or("the chat").to_owned();
+
+    // only banned users have anything to appeal
+    let is_banned = matches!(
+        bot.get_chat_member(chat.id, user.id)
+            .await
+            .map(|member| member.status()),
+        Ok(ChatMemberStatus::Banned)
+    );
+    if !is_banned {
+        bot.send_message(message.chat.id, "You aren't banned from that chat!")
+            .reply_to_message_id(message.id)
+            .await?;
+        return Ok(());
+    }
+
+    let (count, pending) = appeals::count_appeals(chat.id, user.id, pool).await?;
+    if pending {
+        bot.send_message(
+            message.chat.id,
+            "You already have an appeal waiting on the admins of that chat, hang tight.",
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(());
+    }
+    if count >= MAX_APPEALS {
+        bot.send_message(
+            message.chat.id,
+            "You've used up all your appeals for that chat.",
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(());
+    }
+
+    chats::insert_chat(chat.id, Some(chat_title.clone()), pool).await?;
+    // a new appeal replaces any that went unanswered
+    appeals::withdraw_appeals(chat.id, user.id, pool).await?;
+    let appeal_id = appeals::create_appeal(chat.id, user.id, reason.clone(), pool).await?;
+
+    let mut text = format!(
+        "{} ({}) appealed their ban in {} (appeal {} of {}).",
+        html::user_mention(user.id, &html::escape(&user.full_name())),
+        html::code_inline(&user.id.to_string()),
+        html::bold(&html::escape(&chat_title)),
+        count + 1,
+        MAX_APPEALS
+    );
+    if let Some(reason) = &reason {
+        text.push_str(&format!("\nReason: {}", html::escape(reason)));
+    }
+
+    // forward the appeal to every admin in their PMs, this only works if they've started the bot
+    let admins = admincache::get_admins(bot, chat.id).await?;
+    let results = join_all(
+        admins
+            .iter()
+            .filter(|admin| !admin.user.is_bot)
+            .map(|admin| {
+                bot.send_message(admin.user.id, text.clone())
+                    .reply_markup(appeal_keyboard(appeal_id))
+                    .send()
+            }),
+    )
+    .await;
+
+    // post it in the chat itself if no admin could be reached
+    if !results.iter().any(|res| res.is_ok()) {
+        bot.send_message(chat.id, text)
+            .reply_markup(appeal_keyboard(appeal_id))
+            .await?;
+    }
+
+    bot.send_message(
+        message.chat.id,
+        format!(
+            "Your appeal has been sent to the admins of {}. I'll let you know once they've decided.",
+            html::bold(&html::escape(&chat_title))
+        ),
+    )
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn handle_callback(
+    bot: &crate::Bot,
+    query: &CallbackQuery,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let data = query.data.as_deref().unwrap_or_default();
+    let parts: Vec<_> = data.split(':').collect();
+    if parts.len() != 3 {
+        return Ok(());
+    }
+
+    let action = parts[1];
+    let appeal = match appeals::get_appeal(parts[2].parse::<i32>()?, pool).await? {
+        Some(appeal) => appeal,
+        None => {
+            bot.answer_callback_query(query.id.clone())
+                .text("This appeal no longer exists.")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    // only people who could have unbanned the user can decide
+    if !perms::can_user_restrict_members(bot, appeal.chat_id, query.from.id).await? {
+        bot.answer_callback_query(query.id.clone())
+            .text("You're missing the required permission for this action: CAN_RESTRICT_MEMBERS.")
+            .show_alert(true)
+            .await?;
+        return Ok(());
+    }
+
+    if appeal.status != "pending" {
+        bot.answer_callback_query(query.id.clone())
+            .text(format!("This appeal was already {}.", appeal.status))
+            .show_alert(true)
+            .await?;
+        return Ok(());
+    }
+
+    let (status, outcome) = match action {
+        "unban" => ("accepted", "Unbanned!"),
+        _ => ("rejected", "Rejected!"),
+    };
+
+    // another admin may have beaten us to it
+    if !appeals::decide_appeal(appeal.appeal_id, status, pool).await? {
+        bot.answer_callback_query(query.id.clone())
+            .text("This appeal was already decided.")
+            .show_alert(true)
+            .await?;
+        return Ok(());
+    }
+
+    if status == "accepted" {
+        if let Err(err) = banning::unban_user(bot, appeal.chat_id, appeal.user_id).await {
+            // leave it up to the admins to try again
+            appeals::reopen_appeal(appeal.appeal_id, pool).await?;
+            bot.answer_callback_query(query.id.clone())
+                .text(format!("Failed: {}", err))
+                .show_alert(true)
+                .await?;
+            return Ok(());
+        }
+    }
+
+    bot.answer_callback_query(query.id.clone())
+        .text(outcome)
+        .await?;
+
+    // let the user know what the admins decided
+    let chat_title = chats::get_chat(appeal.chat_id, pool)
+        .await
+        .ok()
+        .and_then(|chat| chat.chat_name)
+        .unwrap_or_else(|| "the chat".to_owned());
+    let decision = match status {
+        "accepted" => format!(
+            "Your appeal in {} was accepted, you've been unbanned!",
+            html::bold(&html::escape(&chat_title))
+        ),
+        _ => format!(
+            "Your appeal in {} was rejected.",
+            html::bold(&html::escape(&chat_title))
+        ),
+    };
+    bot.send_message(appeal.user_id, decision).await.ok();
+
+    // record what happened on the appeal itself
+    if let Some(appeal_msg) = &query.message {
+        let text = format!(
+            "{}\n\n{} ({})",
+            html::escape(appeal_msg.text().unwrap_or_default()),
+            outcome,
+            html::user_mention(query.from.id, &html::escape(&query.from.first_name)),
+        );
+        bot.edit_message_text(appeal_msg.chat.id, appeal_msg.id, text)
+            .await
+            .ok();
+    }
+
+    Ok(())
+}
@@ -0,0 +1,284 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use sqlx::{Pool, Postgres};
+use teloxide::{
+    payloads::SendMessageSetters,
+    prelude2::*,
+    types::{InlineKeyboardButton, InlineKeyboardMarkup},
+    utils::html,
+};
+
+use crate::{
+    handlers::{banning, muting},
+    repo::approvals,
+    utils::{self, perms},
+    BOT_ID,
+};
+
+pub async fn approve(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    // check for required conditions
+    tokio::try_join!(
+        perms::require_group_or_connection(bot, message), // needs a group or a connected PM
+        perms::require_user_admin(bot, message),          // user requires admin permissions
+    )?;
+
+    let chat_id = utils::target_chat(message, pool).await?;
+
+    // extract user from message
+    let (user_id, _) = utils::extract_user_and_text(bot, message, pool).await;
+    let user_id = match user_id {
+        Some(id) => id,
+        None => {
+            bot.send_message(message.chat.id, "Try targeting a user next time bud.")
+                .reply_to_message_id(message.id)
+                .await?;
+            return Ok(());
+        }
+    };
+
+    // user is a dumbass
+    if user_id == *BOT_ID {
+        bot.send_message(message.chat.id, "No u")
+            .reply_to_message_id(message.id)
+            .await?;
+        return Ok(());
+    }
+
+    // admins don't need approval
+    if perms::is_user_admin(bot, message, user_id).await.is_ok() {
+        bot.send_message(
+            message.chat.id,
+            "This user is an admin, they don't need to be approved.",
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(());
+    }
+
+    approvals::approve_user(chat_id, user_id, pool).await?;
+
+    bot.send_message(
+        message.chat.id,
+        "Approved! They will now be ignored by automated moderation.",
+    )
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn unapprove(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    // check for required conditions
+    tokio::try_join!(
+        perms::require_group_or_connection(bot, message), // needs a group or a connected PM
+        perms::require_user_admin(bot, message),          // user requires admin permissions
+    )?;
+
+    let chat_id = utils::target_chat(message, pool).await?;
+
+    // extract user from message
+    let (user_id, _) = utils::extract_user_and_text(bot, message, pool).await;
+    let user_id = match user_id {
+        Some(id) => id,
+        None => {
+            bot.send_message(message.chat.id, "Try targeting a user next time bud.")
+                .reply_to_message_id(message.id)
+                .await?;
+            return Ok(());
+        }
+    };
+
+    if approvals::unapprove_user(chat_id, user_id, pool).await? {
+        bot.send_message(message.chat.id, "Unapproved!")
+            .reply_to_message_id(message.id)
+            .await?;
+    } else {
+        bot.send_message(message.chat.id, "This user wasn't approved!")
+            .reply_to_message_id(message.id)
+            .await?;
+    }
+
+    Ok(())
+}
+
+pub async fn approved(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    // check for required conditions
+    tokio::try_join!(
+        perms::require_group_or_connection(bot, message), // needs a group or a connected PM
+        perms::require_user_admin(bot, message),          // user requires admin permissions
+    )?;
+
+    let chat_id = utils::target_chat(message, pool).await?;
+
+    let users = approvals::get_approved_users(chat_id, pool).await?;
+    if users.is_empty() {
+        bot.send_message(message.chat.id, "No users are approved in this chat.")
+            .reply_to_message_id(message.id)
+            .await?;
+        return Ok(());
+    }
+
+    let list: Vec<_> = users
+        .iter()
+        .map(|(user_id, full_name)| {
+            format!(
+                "- {} ({})",
+                html::user_mention(
+                    *user_id,
+                    &html::escape(full_name.as_deref().unwrap_or("Unknown user"))
+                ),
+                html::code_inline(&user_id.to_string())
+            )
+        })
+        .collect();
+
+    bot.send_message(
+        message.chat.id,
+        format!("Approved users in this chat:\n{}", list.join("\n")),
+    )
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn unapprove_all(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    // check for required conditions
+    tokio::try_join!(
+        perms::require_group(bot, message), // command needs to be in a public group
+        perms::require_chat_owner(bot, message), // only the owner can clear approvals
+    )?;
+
+    let count = approvals::unapprove_all(message.chat.id, pool).await?;
+
+    bot.send_message(message.chat.id, format!("Unapproved {} user(s).", count))
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
+
+// ask for confirmation before acting against an approved user
+pub async fn confirm_action(
+    bot: &crate::Bot,
+    message: &Message,
+    action: &str,
+    user_id: i64,
+    until_date: Option<DateTime<Utc>>,
+) -> anyhow::Result<()> {
+    let until = until_date.map(|d| d.timestamp()).unwrap_or(0);
+    let keyboard = InlineKeyboardMarkup::default().append_row(vec![
+        InlineKeyboardButton::callback(
+            format!("Yes, {}", action),
+            format!("approval:{}:{}:{}", action, user_id, until),
+        ),
+        InlineKeyboardButton::callback("Cancel".to_owned(), "approval:cancel".to_owned()),
+    ]);
+
+    bot.send_message(
+        message.chat.id,
+        format!(
+            "This user is approved in this chat. Are you sure you want to {} them?",
+            action
+        ),
+    )
+    .reply_markup(keyboard)
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn handle_callback(
+    bot: &crate::Bot,
+    query: &CallbackQuery,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let confirm_msg = match &query.message {
+        Some(message) => message,
+        None => return Ok(()),
+    };
+    let chat_id = confirm_msg.chat.id;
+
+    // only people who could have issued the command can confirm it
+    if !perms::can_restrict_from_reply(bot, confirm_msg, query.from.id).await? {
+        bot.answer_callback_query(query.id.clone())
+            .text("You're missing the required permission for this action: CAN_RESTRICT_MEMBERS.")
+            .show_alert(true)
+            .await?;
+        return Ok(());
+    }
+
+    let data = query.data.as_deref().unwrap_or_default();
+    let parts: Vec<_> = data.split(':').collect();
+    let mut confirmed = None;
+    let outcome = if parts.len() == 4 {
+        let user_id = parts[2].parse::<i64>()?;
+        let until_date = match parts[3].parse::<i64>()? {
+            0 => None,
+            ts => Some(DateTime::<Utc>::from_utc(
+                NaiveDateTime::from_timestamp(ts, 0),
+                Utc,
+            )),
+        };
+
+        let res = match parts[1] {
+            "ban" => banning::ban_user(bot, chat_id, user_id, until_date)
+                .await
+                .map(|_| "Banned!"),
+            "kick" => banning::kick_user(bot, chat_id, user_id)
+                .await
+                .map(|_| "Kicked!"),
+            "mute" => muting::mute_user(bot, chat_id, user_id, until_date)
+                .await
+                .map(|_| "Muted!"),
+            _ => Ok("Cancelled."),
+        };
+
+        match res {
+            Ok(text) => {
+                confirmed = Some((parts[1], user_id, until_date));
+                text.to_owned()
+            }
+            Err(err) => format!("Failed: {}", err),
+        }
+    } else {
+        "Cancelled.".to_owned()
+    };
+
+    bot.answer_callback_query(query.id.clone()).await?;
+    bot.edit_message_text(chat_id, confirm_msg.id, outcome)
+        .await?;
+
+    // the confirmed command carries on into linked chats, like it would have right away
+    if let (Some((action, user_id, until_date)), Some(command_msg)) =
+        (confirmed, confirm_msg.reply_to_message())
+    {
+        match action {
+            "ban" => {
+                banning::ban_in_linked_chats(bot, command_msg, &[user_id], until_date, pool).await?
+            }
+            "mute" => {
+                muting::mute_in_linked_chats(bot, command_msg, &[user_id], until_date, pool).await?
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
@@ -1,17 +1,211 @@
-use std::convert::TryInto;
-
-use chrono::Duration;
+use chrono::{DateTime, Utc};
 use sqlx::{Pool, Postgres};
 use teloxide::{
+    payloads::SendMessageSetters,
     prelude2::*,
     types::{ChatMember, ChatMemberStatus},
+    utils::html,
 };
 
 use anyhow::anyhow;
 
-use crate::{utils::UnitOfTime, BOT_ID};
+use crate::{utils::FilterAction, BOT_ID};
+
+use crate::{
+    handlers::{approvals, linking, muting},
+    repo::appeals,
+    utils::{self, perms},
+};
+
+// ban a user from a chat, optionally only until the specified time
+pub async fn ban_user(
+    bot: &crate::Bot,
+    chat_id: i64,
+    user_id: i64,
+    until_date: Option<DateTime<Utc>>,
+) -> anyhow::Result<()> {
+    // channels can only be banned for good, refuse instead of quietly dropping the end time
+    if utils::is_channel(user_id) {
+        if until_date.is_some() {
+            anyhow::bail!("Channels can't be banned temporarily, use /ban instead.");
+        }
+        bot.ban_chat_sender_chat(chat_id, user_id).await?;
+        return Ok(());
+    }
+
+    match until_date {
+        Some(until_date) => {
+            bot.kick_chat_member(chat_id, user_id)
+                .until_date(until_date)
+                .await?
+        }
+        None => bot.kick_chat_member(chat_id, user_id).await?,
+    };
+
+    Ok(())
+}
+
+// remove a user from a chat without banning them
+pub async fn kick_user(bot: &crate::Bot, chat_id: i64, user_id: i64) -> anyhow::Result<()> {
+    // calling unban on a user in the chat bans and immediately unbans them
+    bot.unban_chat_member(chat_id, user_id).await?;
+
+    Ok(())
+}
+
+// lift a ban, leaving users that are still in the chat alone
+pub async fn unban_user(bot: &crate::Bot, chat_id: i64, user_id: i64) -> anyhow::Result<()> {
+    if utils::is_channel(user_id) {
+        bot.unban_chat_sender_chat(chat_id, user_id).await?;
+        return Ok(());
+    }
+
+    bot.unban_chat_member(chat_id, user_id)
+        .only_if_banned(true)
+        .await?;
+
+    Ok(())
+}
+
+// apply an automated moderation action to a user, returning false if it doesn't apply to them
+pub async fn punish_user(
+    bot: &crate::Bot,
+    chat_id: i64,
+    user_id: i64,
+    action: FilterAction,
+) -> anyhow::Result<bool> {
+    match action {
+        FilterAction::Ban => ban_user(bot, chat_id, user_id, None).await?,
+        // channels can't be kicked or muted, deleting their message has to do
+        FilterAction::Kick | FilterAction::Mute if utils::is_channel(user_id) => return Ok(false),
+        FilterAction::Kick => kick_user(bot, chat_id, user_id).await?,
+        FilterAction::Mute => muting::mute_user(bot, chat_id, user_id, None).await?,
+    }
+
+    Ok(true)
+}
+
+// make sure a user can be acted on as part of a mass action, returning their membership
+pub async fn check_mass_target(
+    bot: &crate::Bot,
+    chat_id: i64,
+    user_id: i64,
+    pool: &Pool<Postgres>,
+) -> Result<ChatMember, String> {
+    if user_id == *BOT_ID {
+        return Err("That's me!".to_owned());
+    }
+
+    if utils::is_channel(user_id) {
+        return Err("Channels can only be banned or unbanned.".to_owned());
+    }
+
+    let chat_member = bot
+        .get_chat_member(chat_id, user_id)
+        .await
+        .map_err(|_| "This user is ded mate.".to_owned())?;
+
+    if matches!(
+        chat_member.status(),
+        ChatMemberStatus::Administrator | ChatMemberStatus::Owner
+    ) {
+        return Err("This user is an administrator.".to_owned());
+    }
+
+    // approved users need confirmation, which only works one at a time
+    if perms::is_user_approved(chat_id, user_id, pool)
+        .await
+        .map_err(|err| html::escape(&err.to_string()))?
+    {
+        return Err("This user is approved, target them on their own to confirm.".to_owned());
+    }
+
+    Ok(chat_member)
+}
+
+// parse when a temporary action should end, letting the user know if it's missing or invalid
+pub async fn parse_until(
+    bot: &crate::Bot,
+    message: &Message,
+    args: Option<String>,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<Option<(DateTime<Utc>, String)>> {
+    let args = match args {
+        Some(args) => args,
+        None => {
+            bot.send_message(
+                message.chat.id,
+                "You need to specify a duration or end time, eg: 1d12h or until friday 9am",
+            )
+            .reply_to_message_id(message.id)
+            .await?;
+            return Ok(None);
+        }
+    };
 
-use crate::utils::{self, perms};
+    let tz = utils::chat_timezone(message.chat.id, pool).await?;
+    match utils::parse_end_time(&args, message.date, tz) {
+        Ok(end) => Ok(Some(end)),
+        Err(err) => {
+            bot.send_message(message.chat.id, err)
+                .reply_to_message_id(message.id)
+                .await?;
+            Ok(None)
+        }
+    }
+}
+
+async fn mass_ban(
+    bot: &crate::Bot,
+    message: &Message,
+    targets: Vec<Result<i64, String>>,
+    until_date: Option<DateTime<Utc>>,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let chat_id = message.chat.id;
+    let (summary, banned) = utils::mass_action(targets, "Banned", |user_id| async move {
+        if !utils::is_channel(user_id) {
+            check_mass_target(bot, chat_id, user_id, pool).await?;
+        }
+        utils::retry_rate_limited(|| ban_user(bot, chat_id, user_id, until_date))
+            .await
+            .map_err(|err| html::escape(&err.to_string()))
+    })
+    .await;
+
+    bot.send_message(chat_id, summary)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    ban_in_linked_chats(bot, message, &banned, until_date, pool).await
+}
+
+// ban users in the chats linked with the current one as well
+pub async fn ban_in_linked_chats(
+    bot: &crate::Bot,
+    message: &Message,
+    user_ids: &[i64],
+    until_date: Option<DateTime<Utc>>,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    linking::apply_to_linked(
+        bot,
+        message,
+        "Banned",
+        user_ids,
+        |chat_id, user_id| async move {
+            // channels aren't members, so there's nothing to check
+            if !utils::is_channel(user_id) {
+                linking::check_linked_target(bot, chat_id, user_id, pool).await?;
+            }
+            utils::retry_rate_limited(|| ban_user(bot, chat_id, user_id, until_date))
+                .await
+                .map_err(|err| html::escape(&err.to_string()))
+        },
+        pool,
+    )
+    .await
+}
 
 pub async fn ban(
     bot: &crate::Bot,
@@ -28,8 +222,30 @@ pub async fn ban(
         perms::require_bot_restrict_chat_members(bot, message) // bot requires RESTRICT_CHAT_MEMBERS permissions
     )?;
 
-    // extract user and text from message
-    let (user_id, args) = utils::extract_user_and_text(bot, message, pool).await;
+    // several users can be banned at once
+    let (mut targets, args) = utils::extract_users_and_text(message, pool).await;
+    if targets.len() > 1 {
+        let until_date = match is_tban {
+            true => match parse_until(bot, message, args, pool).await? {
+                Some((until_date, _)) => Some(until_date),
+                None => return Ok(()),
+            },
+            false => None,
+        };
+        return mass_ban(bot, message, targets, until_date, pool).await;
+    }
+
+    // a single target was already resolved above
+    let user_id = match targets.pop() {
+        Some(Ok(user_id)) => Some(user_id),
+        Some(Err(reason)) => {
+            bot.send_message(message.chat.id, reason)
+                .reply_to_message_id(message.id)
+                .await?;
+            return Ok(());
+        }
+        None => None,
+    };
     if user_id.is_none() {
         // no user was targeted
         bot.send_message(message.chat.id, "Try targeting a user next time bud.")
@@ -41,12 +257,32 @@ pub async fn ban(
     if args.is_none() && is_tban {
         bot.send_message(
             message.chat.id,
-            "You need to specify a duration in d/h/m/s (days, hours, minutes, seconds)",
+            "You need to specify a duration or end time, eg: 1d12h or until friday 9am",
         )
         .await?;
         return Ok(());
     }
 
+    // channels posting in the chat are banned as a whole
+    if utils::is_channel(user_id.unwrap()) {
+        if is_tban {
+            bot.send_message(
+                message.chat.id,
+                "Channels can't be banned temporarily, use /ban instead.",
+            )
+            .await?;
+            return Ok(());
+        }
+
+        ban_user(bot, chat.id, user_id.unwrap(), None).await?;
+        bot.send_message(
+            message.chat.id,
+            "Banned the channel! Nobody can post as it here anymore.",
+        )
+        .await?;
+        return ban_in_linked_chats(bot, message, &[user_id.unwrap()], None, pool).await;
+    }
+
     // check if user is valid
     let chat_member: ChatMember = match bot.get_chat_member(chat.id, user_id.unwrap()).await {
         Ok(m) => m, // user is valid
@@ -73,35 +309,47 @@ pub async fn ban(
         return Ok(());
     }
 
-    if let Some(args) = args {
-        if is_tban {
-            // get unit of time
-            let unit = args.parse::<UnitOfTime>();
-            if unit.is_err() {
-                bot.send_message(message.chat.id, "failed to get specified time; expected one of d/h/m/s (days, hours, minutes, seconds)").await?;
-                return Ok(());
-            }
-
-            // convert to seconds
-            let time = utils::extract_time(unit.as_ref().unwrap());
-            let until_time = message
-                .date
-                .checked_add_signed(Duration::seconds(time.try_into().unwrap()))
-                .ok_or(anyhow!("Something went wrong!"))?;
-
-            // ban chat member for specified time
-            bot.kick_chat_member(chat.id, user_id.unwrap())
-                .until_date(until_time)
-                .await?;
-            bot.send_message(message.chat.id, format!("Banned for {}!", unit.unwrap()))
-                .await?;
+    if is_tban {
+        // get when the ban should end
+        let tz = utils::chat_timezone(chat.id, pool).await?;
+        let (until_time, until_desc) =
+            match utils::parse_end_time(&args.unwrap_or_default(), message.date, tz) {
+                Ok(end) => end,
+                Err(err) => {
+                    bot.send_message(message.chat.id, err).await?;
+                    return Ok(());
+                }
+            };
+
+        // approved users need confirmation
+        if perms::is_user_approved(chat.id, user_id.unwrap(), pool).await? {
+            return approvals::confirm_action(
+                bot,
+                message,
+                "ban",
+                user_id.unwrap(),
+                Some(until_time),
+            )
+            .await;
         }
+
+        // ban chat member for specified time
+        ban_user(bot, chat.id, user_id.unwrap(), Some(until_time)).await?;
+        bot.send_message(message.chat.id, format!("Banned {}!", until_desc))
+            .await?;
+        ban_in_linked_chats(bot, message, &[user_id.unwrap()], Some(until_time), pool).await?;
     } else {
+        // approved users need confirmation
+        if perms::is_user_approved(chat.id, user_id.unwrap(), pool).await? {
+            return approvals::confirm_action(bot, message, "ban", user_id.unwrap(), None).await;
+        }
+
         // permanently ban chat member
-        bot.kick_chat_member(chat.id, user_id.unwrap()).await?;
+        ban_user(bot, chat.id, user_id.unwrap(), None).await?;
 
         // let user know something happened
         bot.send_message(message.chat.id, "Banned!").await?;
+        ban_in_linked_chats(bot, message, &[user_id.unwrap()], None, pool).await?;
     }
 
     Ok(())
@@ -121,14 +369,54 @@ pub async fn kick(
         perms::require_bot_restrict_chat_members(bot, message) // bot requires RESTRICT_CHAT_MEMBERS permissions
     )?;
 
-    // extract user and text from message
-    let (user_id, _) = utils::extract_user_and_text(bot, message, pool).await;
+    // several users can be kicked at once
+    let (mut targets, _) = utils::extract_users_and_text(message, pool).await;
+    if targets.len() > 1 {
+        let chat_id = chat.id;
+        let (summary, _) = utils::mass_action(targets, "Kicked", |user_id| async move {
+            let chat_member = check_mass_target(bot, chat_id, user_id, pool).await?;
+            if matches!(
+                chat_member.status(),
+                ChatMemberStatus::Banned | ChatMemberStatus::Left
+            ) {
+                return Err("This user isn't in the chat!".to_owned());
+            }
+            utils::retry_rate_limited(|| kick_user(bot, chat_id, user_id))
+                .await
+                .map_err(|err| html::escape(&err.to_string()))
+        })
+        .await;
+
+        bot.send_message(chat_id, summary)
+            .reply_to_message_id(message.id)
+            .await?;
+        return Ok(());
+    }
+
+    // a single target was already resolved above
+    let user_id = match targets.pop() {
+        Some(Ok(user_id)) => Some(user_id),
+        Some(Err(reason)) => {
+            bot.send_message(message.chat.id, reason)
+                .reply_to_message_id(message.id)
+                .await?;
+            return Ok(());
+        }
+        None => None,
+    };
     if user_id.is_none() {
         bot.send_message(message.chat.id, "Try targeting a user next time bud.")
             .await?;
         return Ok(());
     }
 
+    // channels can't be kicked
+    if utils::is_channel(user_id.unwrap()) {
+        bot.send_message(message.chat.id, "Channels can't be kicked, only banned.")
+            .await?;
+        return Ok(());
+    }
+
     // check if user is valid
     let chat_member: ChatMember = match bot.get_chat_member(chat.id, user_id.unwrap()).await {
         Ok(m) => m, // user is valid
@@ -161,9 +449,13 @@ pub async fn kick(
         return Ok(());
     }
 
+    // approved users need confirmation
+    if perms::is_user_approved(chat.id, user_id.unwrap(), pool).await? {
+        return approvals::confirm_action(bot, message, "kick", user_id.unwrap(), None).await;
+    }
+
     // kick the user
-    // calling unban on a user in the chat bans and immediately unbans them
-    bot.unban_chat_member(chat.id, user_id.unwrap()).await?;
+    kick_user(bot, chat.id, user_id.unwrap()).await?;
 
     // let the user know something happened
     bot.send_message(message.chat.id, "Kicked!").await?;
@@ -193,8 +485,7 @@ pub async fn kickme(bot: &crate::Bot, message: &Message) -> anyhow::Result<()> {
         .await?;
 
     // kick the user
-    // calling unban on a user in the chat bans and immediately unbans them
-    bot.unban_chat_member(message.chat.id, user.id).await?;
+    kick_user(bot, message.chat.id, user.id).await?;
 
     Ok(())
 }
@@ -213,14 +504,62 @@ pub async fn unban(
         perms::require_bot_restrict_chat_members(bot, message) // bot requires RESTRICT_CHAT_MEMBERS permissions
     )?;
 
-    // extract user and text from message
-    let (user_id, _) = utils::extract_user_and_text(bot, message, pool).await;
+    // several users can be unbanned at once
+    let (mut targets, _) = utils::extract_users_and_text(message, pool).await;
+    if targets.len() > 1 {
+        let chat_id = chat.id;
+        let (summary, _) = utils::mass_action(targets, "Unbanned", |user_id| async move {
+            if utils::is_channel(user_id) {
+                return utils::retry_rate_limited(|| unban_user(bot, chat_id, user_id))
+                    .await
+                    .map_err(|err| html::escape(&err.to_string()));
+            }
+            let chat_member = bot
+                .get_chat_member(chat_id, user_id)
+                .await
+                .map_err(|_| "This user is ded mate.".to_owned())?;
+            if !matches!(chat_member.status(), ChatMemberStatus::Banned) {
+                return Err("This user wasn't banned!".to_owned());
+            }
+            utils::retry_rate_limited(|| unban_user(bot, chat_id, user_id))
+                .await
+                .map_err(|err| html::escape(&err.to_string()))?;
+            appeals::withdraw_appeals(chat_id, user_id, pool).await.ok();
+            Ok(())
+        })
+        .await;
+
+        bot.send_message(chat_id, summary)
+            .reply_to_message_id(message.id)
+            .await?;
+        return Ok(());
+    }
+
+    // a single target was already resolved above
+    let user_id = match targets.pop() {
+        Some(Ok(user_id)) => Some(user_id),
+        Some(Err(reason)) => {
+            bot.send_message(message.chat.id, reason)
+                .reply_to_message_id(message.id)
+                .await?;
+            return Ok(());
+        }
+        None => None,
+    };
     if user_id.is_none() {
         bot.send_message(message.chat.id, "Try targeting a user next time bud.")
             .await?;
         return Ok(());
     }
 
+    // channels aren't chat members, so there's nothing to check
+    if utils::is_channel(user_id.unwrap()) {
+        unban_user(bot, chat.id, user_id.unwrap()).await?;
+        bot.send_message(message.chat.id, "Unbanned the channel!")
+            .await?;
+        return Ok(());
+    }
+
     // check if user is valid
     let chat_member: ChatMember = match bot.get_chat_member(chat.id, user_id.unwrap()).await {
         Ok(m) => m, // user is valid
@@ -245,8 +584,9 @@ pub async fn unban(
         return Ok(());
     }
 
-    // unban the user
-    bot.unban_chat_member(chat.id, user_id.unwrap()).await?;
+    // unban the user, their appeals have nothing left to decide
+    unban_user(bot, chat.id, user_id.unwrap()).await?;
+    appeals::withdraw_appeals(chat.id, user_id.unwrap(), pool).await?;
 
     // let user know something happened
     bot.send_message(message.chat.id, "Unbanned!").await?;
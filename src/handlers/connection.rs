@@ -0,0 +1,183 @@
+use sqlx::{Pool, Postgres};
+use teloxide::{payloads::SendMessageSetters, prelude2::*, utils::html};
+
+use crate::{
+    repo::{chats, connections},
+    utils::perms,
+};
+
+// commands that apply to the connected chat when sent in PM; actions on users like /ban or
+// /purge need a message to reply to or delete, so they have to be sent in the group itself
+const CONNECTED_COMMANDS: [&str; 24] = [
+    "addmod",
+    "adminlist",
+    "allowdomain",
+    "antichannel",
+    "antidupe",
+    "antiraid",
+    "approve",
+    "approved",
+    "blockdomain",
+    "botperms",
+    "disable",
+    "disabled",
+    "enable",
+    "linkmode",
+    "mods",
+    "namefilter",
+    "nightmode",
+    "perms",
+    "presets",
+    "reports",
+    "rmmod",
+    "scriptfilter",
+    "settimezone",
+    "unapprove",
+];
+
+pub async fn connect(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let user = match message.from() {
+        Some(user) => user,
+        None => return Ok(()),
+    };
+
+    // in a group, connect to that group; in PM, to the chat given as an argument
+    let chat = if message.chat.is_private() {
+        let chat_arg = match message
+            .text()
+            .and_then(|text| text.split_whitespace().nth(1))
+        {
+            Some(chat_arg) => chat_arg,
+            None => {
+                bot.send_message(
+                    message.chat.id,
+                    "Usage: <code>/connect &lt;chat ID or @username&gt;</code>, or send /connect in the chat itself",
+                )
+                .reply_to_message_id(message.id)
+                .await?;
+                return Ok(());
+            }
+        };
+
+        let chat = match chat_arg.parse::<i64>() {
+            Ok(chat_id) => bot.get_chat(chat_id).await,
+            Err(_) => bot.get_chat(chat_arg.to_owned()).await,
+        };
+        match chat {
+            Ok(chat) if !chat.is_private() => chat,
+            _ => {
+                bot.send_message(
+                    message.chat.id,
+                    "I couldn't find that chat; are you sure I'm in it?",
+                )
+                .reply_to_message_id(message.id)
+                .await?;
+                return Ok(());
+            }
+        }
+    } else {
+        message.chat.clone()
+    };
+
+    // only admins get to manage a chat from afar
+    if !perms::is_chat_admin(bot, chat.id, user.id).await? {
+        bot.send_message(
+            message.chat.id,
+            "You need to be an admin of that chat to connect to it.",
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(());
+    }
+
+    let chat_title = chat.title().unwrap_or("the chat").to_owned();
+    chats::insert_chat(chat.id, Some(chat_title.clone()), pool).await?;
+    connections::set_connection(user.id, chat.id, pool).await?;
+
+    let commands: Vec<_> = CONNECTED_COMMANDS
+        .iter()
+        .map(|command| format!("/{}", command))
+        .collect();
+    let text = format!(
+        "Connected to {}. These commands will now apply to it when you send them to me in PM:\n{}\n\nActions on users like /ban, /mute, /kick or /purge still have to be sent in the group. Use /disconnect to stop.",
+        html::bold(&html::escape(&chat_title)),
+        commands.join(", ")
+    );
+    if message.chat.is_private() {
+        bot.send_message(message.chat.id, text)
+            .reply_to_message_id(message.id)
+            .await?;
+    } else {
+        // keep the group quiet, but let them know in case they never started the bot
+        if bot.send_message(user.id, text).await.is_err() {
+            bot.send_message(
+                message.chat.id,
+                "Connected! Start a chat with me so I can take your commands in PM.",
+            )
+            .reply_to_message_id(message.id)
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn disconnect(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let user = match message.from() {
+        Some(user) => user,
+        None => return Ok(()),
+    };
+
+    let text = if connections::remove_connection(user.id, pool).await? {
+        "Disconnected, your commands in PM no longer apply to any chat."
+    } else {
+        "You aren't connected to any chat."
+    };
+
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn connection(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let user = match message.from() {
+        Some(user) => user,
+        None => return Ok(()),
+    };
+
+    let text = match connections::get_connection(user.id, pool).await? {
+        Some(chat_id) => {
+            let chat_title = chats::get_chat(chat_id, pool)
+                .await
+                .ok()
+                .and_then(|chat| chat.chat_name)
+                .unwrap_or_else(|| "a chat".to_owned());
+            format!(
+                "You're connected to {} ({}).",
+                html::bold(&html::escape(&chat_title)),
+                html::code_inline(&chat_id.to_string())
+            )
+        }
+        None => "You aren't connected to any chat.".to_owned(),
+    };
+
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
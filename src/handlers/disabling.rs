@@ -0,0 +1,214 @@
+use sqlx::{Pool, Postgres};
+use teloxide::{payloads::SendMessageSetters, prelude2::*, utils::html};
+
+use crate::{
+    repo::disabled,
+    utils::{self, perms},
+};
+
+// commands anyone can use, and so can be disabled; admin commands are already gated by rights
+pub const DISABLEABLE_COMMANDS: [&str; 8] = [
+    "adminlist",
+    "get",
+    "help",
+    "id",
+    "invitelink",
+    "kickme",
+    "mods",
+    "report",
+];
+
+fn command_list(commands: &[&str]) -> String {
+    commands
+        .iter()
+        .map(|command| format!("/{}", command))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// check if a command was disabled in the chat, deleting it if the chat asked for that
+pub async fn is_disabled(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<bool> {
+    if message.chat.is_private() {
+        return Ok(false);
+    }
+
+    let command = match utils::message_command(message) {
+        Some(command) if DISABLEABLE_COMMANDS.contains(&command.as_str()) => command,
+        _ => return Ok(false),
+    };
+    if !disabled::is_command_disabled(message.chat.id, &command, pool).await? {
+        return Ok(false);
+    }
+
+    let settings = disabled::get_command_settings(message.chat.id, pool).await?;
+    if settings.exempt_admins {
+        // anonymous admins show up as the group itself, so they can't be looked up
+        if utils::is_anonymous_admin(message) {
+            return Ok(false);
+        }
+        if let Some(user) = message.from() {
+            if perms::is_chat_admin(bot, message.chat.id, user.id).await? {
+                return Ok(false);
+            }
+        }
+    }
+
+    if settings.delete_disabled {
+        bot.delete_message(message.chat.id, message.id).await.ok();
+    }
+
+    Ok(true)
+}
+
+// disable or enable the commands given as arguments
+pub async fn toggle_commands(
+    bot: &crate::Bot,
+    message: &Message,
+    disable: bool,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    // check for required conditions
+    tokio::try_join!(
+        perms::require_group_or_connection(bot, message), // needs a group or a connected PM
+        perms::require_user_admin(bot, message),          // user requires admin permissions
+    )?;
+
+    let chat_id = utils::target_chat(message, pool).await?;
+
+    let commands: Vec<_> = message
+        .text()
+        .map(|text| {
+            text.split_whitespace()
+                .skip(1)
+                .map(|command| command.trim_start_matches('/').to_lowercase())
+                .collect()
+        })
+        .unwrap_or_default();
+    if commands.is_empty() {
+        bot.send_message(
+            message.chat.id,
+            format!(
+                "Usage: <code>/{} &lt;command&gt;</code>",
+                if disable { "disable" } else { "enable" }
+            ),
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(());
+    }
+
+    if let Some(command) = commands
+        .iter()
+        .find(|command| !DISABLEABLE_COMMANDS.contains(&command.as_str()))
+    {
+        bot.send_message(
+            message.chat.id,
+            format!(
+                "/{} can't be disabled. These can: {}",
+                html::escape(command),
+                command_list(&DISABLEABLE_COMMANDS)
+            ),
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(());
+    }
+
+    for command in &commands {
+        if disable {
+            disabled::disable_command(chat_id, command, pool).await?;
+        } else {
+            disabled::enable_command(chat_id, command, pool).await?;
+        }
+    }
+
+    let commands: Vec<_> = commands.iter().map(String::as_str).collect();
+    bot.send_message(
+        message.chat.id,
+        format!(
+            "{} {}.",
+            if disable { "Disabled" } else { "Enabled" },
+            command_list(&commands)
+        ),
+    )
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn disabled(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    perms::require_group_or_connection(bot, message).await?;
+
+    let chat_id = utils::target_chat(message, pool).await?;
+
+    let args: Vec<_> = message
+        .text()
+        .map(|text| text.split_whitespace().skip(1).collect())
+        .unwrap_or_default();
+    let mut settings = disabled::get_command_settings(chat_id, pool).await?;
+
+    let text = match args[..] {
+        [] => {
+            let commands = disabled::get_disabled_commands(chat_id, pool).await?;
+            let commands: Vec<_> = commands.iter().map(String::as_str).collect();
+            format!(
+                "Disabled commands: {}\nDeleting disabled commands: {}\nAdmins exempt: {}",
+                match commands.is_empty() {
+                    true => "none".to_owned(),
+                    false => command_list(&commands),
+                },
+                if settings.delete_disabled { "on" } else { "off" },
+                if settings.exempt_admins { "on" } else { "off" }
+            )
+        }
+        [setting @ ("delete" | "exemptadmins"), toggle @ ("on" | "off")] => {
+            perms::require_user_admin(bot, message).await?;
+
+            let enabled = toggle == "on";
+            let text = match (setting, enabled) {
+                ("delete", true) => "Disabled commands will be deleted.",
+                ("delete", false) => "Disabled commands will be left alone.",
+                (_, true) => "Admins can still use disabled commands.",
+                (_, false) => "Disabled commands are disabled for admins too.",
+            };
+            if setting == "delete" {
+                settings.delete_disabled = enabled;
+            } else {
+                settings.exempt_admins = enabled;
+            }
+            disabled::set_command_settings(&settings, pool).await?;
+
+            text.to_owned()
+        }
+        _ => "Usage: <code>/disabled</code>, <code>/disabled delete &lt;on|off&gt;</code> or <code>/disabled exemptadmins &lt;on|off&gt;</code>".to_owned(),
+    };
+
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn disableable(bot: &crate::Bot, message: &Message) -> anyhow::Result<()> {
+    bot.send_message(
+        message.chat.id,
+        format!(
+            "Commands that can be disabled: {}",
+            command_list(&DISABLEABLE_COMMANDS)
+        ),
+    )
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(())
+}
@@ -37,7 +37,7 @@ pub async fn save_note(
 
     let (note_id, note_content) = content.unwrap();
 
-    let chat_id = message.chat.id;
+    let chat_id = utils::target_chat(message, pool).await?;
     let note = Note {
         chat_id,
         note_id: note_id.to_owned(),
@@ -85,7 +85,9 @@ pub async fn get_note(
         note_id = text.unwrap();
     }
 
-    match fetch_note(Some(message.chat.id), Some(&note_id), pool).await {
+    // notes are read from the same chat they're saved to
+    let chat_id = utils::target_chat(message, pool).await?;
+    match fetch_note(Some(chat_id), Some(&note_id), pool).await {
         Ok(note) => {
             bot.send_message(message.chat.id, note.note_content)
                 .reply_to_message_id(message.id)
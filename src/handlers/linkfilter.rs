@@ -0,0 +1,413 @@
+use sqlx::{Pool, Postgres};
+use teloxide::{payloads::SendMessageSetters, prelude2::*, types::MessageEntityKind, utils::html};
+use url::Url;
+
+use crate::{
+    handlers::banning,
+    repo::links,
+    utils::{self, perms, FilterAction},
+};
+
+// normalize a link to the form domains are stored in, eg: 'example.com', 't.me/channel',
+// or 't.me/+' for invite links; internationalized domains end up in punycode
+pub fn normalize_link(link: &str) -> Option<String> {
+    let link = link.trim();
+
+    // links without a scheme are still links
+    let url = match link.contains("://") {
+        true => Url::parse(link),
+        false => Url::parse(&format!("http://{}", link)),
+    }
+    .ok()?;
+    let host = url.host_str()?.trim_end_matches('.').to_lowercase();
+    let host = host.strip_prefix("www.").unwrap_or(&host);
+
+    // tg://join links open invites directly in the app
+    if url.scheme() == "tg" {
+        return Some(match host {
+            "join" => "t.me/+".to_owned(),
+            _ => "t.me".to_owned(),
+        });
+    }
+
+    // telegram links all share a domain, what matters is where they point to
+    if matches!(host, "t.me" | "telegram.me" | "telegram.dog") {
+        let target = url
+            .path_segments()
+            .and_then(|mut segments| segments.next())
+            .unwrap_or_default()
+            .to_lowercase();
+
+        return Some(match target.as_str() {
+            "" => "t.me".to_owned(),
+            "joinchat" => "t.me/+".to_owned(),
+            target if target.starts_with('+') => "t.me/+".to_owned(),
+            target => format!("t.me/{}", target),
+        });
+    }
+
+    Some(host.to_owned())
+}
+
+// check if a link falls under a domain, including its subdomains and paths
+fn matches_domain(link: &str, domain: &str) -> bool {
+    link == domain
+        || link.ends_with(&format!(".{}", domain))
+        || link.starts_with(&format!("{}/", domain))
+}
+
+// every link in a message's text or caption, normalized
+fn message_links(message: &Message) -> Vec<String> {
+    let (text, entities) = match (message.text(), message.entities()) {
+        (Some(text), Some(entities)) => (text, entities),
+        _ => match (message.caption(), message.caption_entities()) {
+            (Some(text), Some(entities)) => (text, entities),
+            _ => return vec![],
+        },
+    };
+
+    entities
+        .iter()
+        .filter_map(|entity| match &entity.kind {
+            MessageEntityKind::Url => normalize_link(
+                &text[utils::byte_offset(text, entity.offset)
+                    ..utils::byte_offset(text, entity.offset + entity.length)],
+            ),
+            MessageEntityKind::TextLink { url } => normalize_link(url.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+// delete messages linking somewhere the chat doesn't allow, returning true if the message was removed
+pub async fn handle_message(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<bool> {
+    if message.chat.is_private() {
+        return Ok(false);
+    }
+
+    let found = message_links(message);
+    if found.is_empty() {
+        return Ok(false);
+    }
+
+    let settings = links::get_link_settings(message.chat.id, pool).await?;
+    let forbidden = match settings.mode.as_str() {
+        "allowlist" => {
+            let allowed = links::get_domains(message.chat.id, true, pool).await?;
+            found
+                .into_iter()
+                .find(|link| !allowed.iter().any(|domain| matches_domain(link, domain)))
+        }
+        "blocklist" => {
+            let blocked = links::get_domains(message.chat.id, false, pool).await?;
+            found
+                .into_iter()
+                .find(|link| blocked.iter().any(|domain| matches_domain(link, domain)))
+        }
+        _ => None,
+    };
+    let forbidden = match forbidden {
+        Some(link) => link,
+        None => return Ok(false),
+    };
+
+    // admins and approved users can link wherever they like
+    let sender_id = match utils::sender_id(message) {
+        Some(sender_id) => sender_id,
+        None => return Ok(false),
+    };
+    if perms::is_user_exempt(bot, message.chat.id, sender_id, pool).await? {
+        return Ok(false);
+    }
+
+    log::info!(
+        "Removing link to {} from {} in chat {}",
+        forbidden,
+        sender_id,
+        message.chat.id
+    );
+    bot.delete_message(message.chat.id, message.id).await.ok();
+
+    if let Some(action) = settings
+        .action
+        .and_then(|action| action.parse::<FilterAction>().ok())
+    {
+        if banning::punish_user(bot, message.chat.id, sender_id, action).await? {
+            bot.send_message(
+                message.chat.id,
+                format!(
+                    "{} was {} for linking to {}.",
+                    utils::sender_mention(message),
+                    action.past_tense(),
+                    html::code_inline(&forbidden)
+                ),
+            )
+            .await?;
+        }
+    }
+
+    Ok(true)
+}
+
+pub async fn link_mode(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    // check for required conditions
+    tokio::try_join!(
+        perms::require_group_or_connection(bot, message), // needs a group or a connected PM
+        perms::require_restrict_chat_members(bot, message), // user requires RESTRICT_CHAT_MEMBERS permissions
+        perms::require_bot_restrict_chat_members(bot, message), // bot requires RESTRICT_CHAT_MEMBERS permissions
+        perms::require_bot_can_delete_messages(bot, message), // bot requires CAN_DELETE_MESSAGES permissions
+    )?;
+
+    let chat_id = utils::target_chat(message, pool).await?;
+
+    let args: Vec<_> = message
+        .text()
+        .map(|text| text.split_whitespace().skip(1).collect())
+        .unwrap_or_default();
+
+    match args[..] {
+        [] => {
+            let settings = links::get_link_settings(chat_id, pool).await?;
+            let allowed = links::get_domains(chat_id, true, pool).await?;
+            let blocked = links::get_domains(chat_id, false, pool).await?;
+            let list = |domains: &[String]| match domains.is_empty() {
+                true => "none".to_owned(),
+                false => domains
+                    .iter()
+                    .map(|domain| html::code_inline(domain))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            };
+
+            bot.send_message(
+                message.chat.id,
+                format!(
+                    "Link filtering mode in this chat: {}{}.\nAllowed: {}\nBlocked: {}",
+                    html::escape(&settings.mode),
+                    match &settings.action {
+                        Some(action) => format!(", offenders get a {}", html::escape(action)),
+                        None => "".to_owned(),
+                    },
+                    list(&allowed),
+                    list(&blocked)
+                ),
+            )
+            .reply_to_message_id(message.id)
+            .await?;
+        }
+        ["off"] => {
+            links::set_link_settings(chat_id, "off", None, pool).await?;
+            bot.send_message(message.chat.id, "Link filtering has been turned off.")
+                .reply_to_message_id(message.id)
+                .await?;
+        }
+        [mode @ ("allowlist" | "blocklist"), ref rest @ ..] if rest.len() <= 1 => {
+            let action = match rest.first().map(|action| action.parse::<FilterAction>()) {
+                Some(Ok(action)) => Some(action),
+                Some(Err(err)) => {
+                    bot.send_message(message.chat.id, err)
+                        .reply_to_message_id(message.id)
+                        .await?;
+                    return Ok(());
+                }
+                None => None,
+            };
+
+            links::set_link_settings(chat_id, mode, action.map(|action| action.to_string()), pool)
+                .await?;
+
+            let mut text = match mode {
+                "allowlist" => "Links to domains outside the allowlist will be deleted".to_owned(),
+                _ => "Links to domains on the blocklist will be deleted".to_owned(),
+            };
+            if let Some(action) = action {
+                text.push_str(&format!(" and their senders {}", action.past_tense()));
+            }
+            text.push('.');
+
+            bot.send_message(message.chat.id, text)
+                .reply_to_message_id(message.id)
+                .await?;
+        }
+        _ => {
+            bot.send_message(
+                message.chat.id,
+                "Usage: <code>/linkmode &lt;allowlist|blocklist&gt; [ban|kick|mute]</code> or <code>/linkmode off</code>",
+            )
+            .reply_to_message_id(message.id)
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+// add or remove domains on the chat's allowlist or blocklist
+pub async fn manage_domains(
+    bot: &crate::Bot,
+    message: &Message,
+    allowed: bool,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    // check for required conditions
+    tokio::try_join!(
+        perms::require_group_or_connection(bot, message), // needs a group or a connected PM
+        perms::require_restrict_chat_members(bot, message), // user requires RESTRICT_CHAT_MEMBERS permissions
+    )?;
+
+    let chat_id = utils::target_chat(message, pool).await?;
+
+    let list_name = if allowed { "allowlist" } else { "blocklist" };
+    let args: Vec<_> = message
+        .text()
+        .map(|text| text.split_whitespace().skip(1).collect())
+        .unwrap_or_default();
+
+    let (remove, domains) = match args[..] {
+        [] => {
+            let domains = links::get_domains(chat_id, allowed, pool).await?;
+            let text = match domains.is_empty() {
+                true => format!("The {} is empty.", list_name),
+                false => format!(
+                    "Domains on the {}:\n{}",
+                    list_name,
+                    domains
+                        .iter()
+                        .map(|domain| format!("- {}", html::code_inline(domain)))
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                ),
+            };
+
+            bot.send_message(message.chat.id, text)
+                .reply_to_message_id(message.id)
+                .await?;
+            return Ok(());
+        }
+        ["remove" | "rm" | "del", ref domains @ ..] => (true, domains),
+        ref domains => (false, domains),
+    };
+
+    let mut changed = vec![];
+    for domain in domains {
+        let domain = match normalize_link(domain) {
+            Some(domain) => domain,
+            None => {
+                bot.send_message(
+                    message.chat.id,
+                    format!("{} isn't a valid domain.", html::code_inline(domain)),
+                )
+                .reply_to_message_id(message.id)
+                .await?;
+                return Ok(());
+            }
+        };
+
+        if remove {
+            if links::remove_domain(chat_id, &domain, allowed, pool).await? {
+                changed.push(html::code_inline(&domain));
+            }
+        } else {
+            links::set_domain(chat_id, &domain, allowed, pool).await?;
+            changed.push(html::code_inline(&domain));
+        }
+    }
+
+    let text = match (remove, changed.is_empty()) {
+        (true, true) => format!("None of those domains were on the {}.", list_name),
+        (true, false) => format!("Removed {} from the {}.", changed.join(", "), list_name),
+        (false, _) => format!("Added {} to the {}.", changed.join(", "), list_name),
+    };
+
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_domains() {
+        assert_eq!(
+            normalize_link("https://www.Example.com/path?q=1"),
+            Some("example.com".to_owned())
+        );
+        assert_eq!(
+            normalize_link("example.com."),
+            Some("example.com".to_owned())
+        );
+        assert_eq!(
+            normalize_link("sub.example.com:8080"),
+            Some("sub.example.com".to_owned())
+        );
+        assert_eq!(normalize_link("not a link"), None);
+    }
+
+    #[test]
+    fn normalize_punycode() {
+        assert_eq!(
+            normalize_link("https://bücher.de"),
+            Some("xn--bcher-kva.de".to_owned())
+        );
+        assert_eq!(
+            normalize_link("xn--bcher-kva.de"),
+            Some("xn--bcher-kva.de".to_owned())
+        );
+    }
+
+    #[test]
+    fn normalize_telegram_links() {
+        assert_eq!(
+            normalize_link("https://t.me/SomeChannel/123"),
+            Some("t.me/somechannel".to_owned())
+        );
+        assert_eq!(
+            normalize_link("telegram.me/somechannel"),
+            Some("t.me/somechannel".to_owned())
+        );
+        assert_eq!(
+            normalize_link("https://t.me/+AbCdEf"),
+            Some("t.me/+".to_owned())
+        );
+        assert_eq!(
+            normalize_link("https://t.me/joinchat/AbCdEf"),
+            Some("t.me/+".to_owned())
+        );
+        assert_eq!(
+            normalize_link("tg://join?invite=AbCdEf"),
+            Some("t.me/+".to_owned())
+        );
+        assert_eq!(normalize_link("https://t.me"), Some("t.me".to_owned()));
+    }
+
+    #[test]
+    fn domain_matching() {
+        assert!(matches_domain("example.com", "example.com"));
+        assert!(matches_domain("sub.example.com", "example.com"));
+        assert!(matches_domain("a.b.example.com", "example.com"));
+        assert!(!matches_domain("badexample.com", "example.com"));
+        assert!(!matches_domain("example.com.evil.org", "example.com"));
+        assert!(!matches_domain("example.com", "sub.example.com"));
+    }
+
+    #[test]
+    fn telegram_link_matching() {
+        assert!(matches_domain("t.me/somechannel", "t.me"));
+        assert!(matches_domain("t.me/+", "t.me"));
+        assert!(matches_domain("t.me/somechannel", "t.me/somechannel"));
+        assert!(!matches_domain("t.me/somechannel2", "t.me/somechannel"));
+        assert!(!matches_domain("t.me", "t.me/somechannel"));
+    }
+}
@@ -0,0 +1,278 @@
+use std::future::Future;
+
+use futures::{stream, StreamExt};
+use sqlx::{Pool, Postgres};
+use teloxide::{
+    payloads::SendMessageSetters,
+    prelude2::*,
+    types::{ChatMember, ChatMemberStatus},
+    utils::html,
+};
+
+use crate::{
+    repo::{chats, linkedchats},
+    utils::perms,
+    BOT_ID,
+};
+
+// number of actions allowed in flight at once in each linked chat
+const MAX_CONCURRENT_ACTIONS: usize = 5;
+
+fn chat_name(chat_id: i64, chat_name: Option<&str>) -> String {
+    match chat_name {
+        Some(name) => html::bold(&html::escape(name)),
+        None => html::code_inline(&chat_id.to_string()),
+    }
+}
+
+// check that a user can be acted on in a linked chat
+pub async fn check_linked_target(
+    bot: &crate::Bot,
+    chat_id: i64,
+    user_id: i64,
+    pool: &Pool<Postgres>,
+) -> Result<ChatMember, String> {
+    let chat_member = bot
+        .get_chat_member(chat_id, user_id)
+        .await
+        .map_err(|_| "I couldn't find them there.".to_owned())?;
+
+    if matches!(
+        chat_member.status(),
+        ChatMemberStatus::Administrator | ChatMemberStatus::Owner
+    ) {
+        return Err("they're an administrator there.".to_owned());
+    }
+
+    // approved users need confirmation, which is only asked in the chat the command was sent in
+    if perms::is_user_approved(chat_id, user_id, pool)
+        .await
+        .map_err(|err| html::escape(&err.to_string()))?
+    {
+        return Err("they're approved there.".to_owned());
+    }
+
+    Ok(chat_member)
+}
+
+// repeat a moderation action against some users in every chat linked with the current one,
+// reporting how it went
+pub async fn apply_to_linked<F, Fut>(
+    bot: &crate::Bot,
+    message: &Message,
+    verb: &str,
+    user_ids: &[i64],
+    action: F,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()>
+where
+    F: Fn(i64, i64) -> Fut,
+    Fut: Future<Output = Result<(), String>>,
+{
+    let actor_id = match message.from() {
+        Some(user) => user.id,
+        None => return Ok(()),
+    };
+    if user_ids.is_empty() {
+        return Ok(());
+    }
+
+    let linked_chats = linkedchats::get_linked_chats(message.chat.id, pool).await?;
+    if linked_chats.is_empty() {
+        return Ok(());
+    }
+
+    let action = &action;
+    let mut results = vec![];
+    for (chat_id, name) in linked_chats {
+        // every chat's own rights apply, being an admin here doesn't make you one there
+        let res = if !perms::can_restrict_in_chat(bot, message, chat_id, actor_id)
+            .await
+            .unwrap_or(false)
+        {
+            Err("you can't restrict members there.".to_owned())
+        } else if !perms::can_user_restrict_members(bot, chat_id, *BOT_ID)
+            .await
+            .unwrap_or(false)
+        {
+            Err("I can't restrict members there.".to_owned())
+        } else if let [user_id] = user_ids {
+            action(chat_id, *user_id).await.map(|_| verb.to_lowercase())
+        } else {
+            let done = stream::iter(user_ids.iter().copied())
+                .map(|user_id| action(chat_id, user_id))
+                .buffer_unordered(MAX_CONCURRENT_ACTIONS)
+                .filter(|res| futures::future::ready(res.is_ok()))
+                .count()
+                .await;
+            Ok(format!(
+                "{} {} of {} user(s)",
+                verb.to_lowercase(),
+                done,
+                user_ids.len()
+            ))
+        };
+
+        results.push(format!(
+            "- {}: {}",
+            chat_name(chat_id, name.as_deref()),
+            match res {
+                Ok(outcome) => outcome,
+                Err(reason) => reason,
+            }
+        ));
+    }
+
+    bot.send_message(
+        message.chat.id,
+        format!("Linked chats:\n{}", results.join("\n")),
+    )
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn link_chat(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    // check for required conditions
+    tokio::try_join!(
+        perms::require_group(bot, message), // command needs to be in a public group
+        perms::require_user_admin(bot, message), // user requires admin permissions
+    )?;
+
+    let user = match message.from() {
+        Some(user) => user,
+        None => return Ok(()),
+    };
+
+    let chat_arg = match message
+        .text()
+        .and_then(|text| text.split_whitespace().nth(1))
+    {
+        Some(chat_arg) => chat_arg,
+        None => {
+            bot.send_message(
+                message.chat.id,
+                "Usage: <code>/linkchat &lt;chat ID or @username&gt;</code>",
+            )
+            .reply_to_message_id(message.id)
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let other = match chat_arg.parse::<i64>() {
+        Ok(chat_id) => bot.get_chat(chat_id).await,
+        Err(_) => bot.get_chat(chat_arg.to_owned()).await,
+    };
+    let other = match other {
+        Ok(chat) if !chat.is_private() => chat,
+        _ => {
+            bot.send_message(
+                message.chat.id,
+                "I couldn't find that chat; are you sure I'm in it?",
+            )
+            .reply_to_message_id(message.id)
+            .await?;
+            return Ok(());
+        }
+    };
+
+    if other.id == message.chat.id {
+        bot.send_message(message.chat.id, "A chat can't be linked to itself.")
+            .reply_to_message_id(message.id)
+            .await?;
+        return Ok(());
+    }
+
+    // linking lets actions here reach the other chat, so it needs the same standing there
+    if !perms::is_chat_admin(bot, other.id, user.id).await? {
+        bot.send_message(
+            message.chat.id,
+            "You need to be an admin of that chat to link it.",
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(());
+    }
+
+    let other_title = other.title().unwrap_or("the chat").to_owned();
+    chats::insert_chat(other.id, Some(other_title.clone()), pool).await?;
+    linkedchats::link_chats(message.chat.id, other.id, pool).await?;
+
+    bot.send_message(
+        message.chat.id,
+        format!(
+            "Linked with {}. Bans and mutes here will also apply there, wherever you and I have the rights.",
+            html::bold(&html::escape(&other_title))
+        ),
+    )
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn unlink_chat(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    // check for required conditions
+    tokio::try_join!(
+        perms::require_group(bot, message), // command needs to be in a public group
+        perms::require_user_admin(bot, message), // user requires admin permissions
+    )?;
+
+    let text = if linkedchats::unlink_chat(message.chat.id, pool).await? {
+        "Unlinked, actions here no longer apply to other chats."
+    } else {
+        "This chat isn't linked to any other chat."
+    };
+
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn linked_chats(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    perms::require_group(bot, message).await?;
+
+    let linked_chats = linkedchats::get_linked_chats(message.chat.id, pool).await?;
+    if linked_chats.is_empty() {
+        bot.send_message(message.chat.id, "This chat isn't linked to any other chat.")
+            .reply_to_message_id(message.id)
+            .await?;
+        return Ok(());
+    }
+
+    let list: Vec<_> = linked_chats
+        .iter()
+        .map(|(chat_id, name)| {
+            format!(
+                "- {} ({})",
+                chat_name(*chat_id, name.as_deref()),
+                html::code_inline(&chat_id.to_string())
+            )
+        })
+        .collect();
+
+    bot.send_message(
+        message.chat.id,
+        format!("This chat is linked with:\n{}", list.join("\n")),
+    )
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(())
+}
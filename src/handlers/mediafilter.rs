@@ -0,0 +1,288 @@
+use sqlx::{Pool, Postgres};
+use teloxide::{payloads::SendMessageSetters, prelude2::*, utils::html};
+
+use crate::{
+    entities::BlockedMedia,
+    handlers::banning,
+    repo::media,
+    utils::{self, perms, FilterAction},
+    OWNER_ID,
+};
+
+// the kind of media in a message and the unique IDs of its files, the last one being the
+// one that gets blocked; photos come in several sizes that each have their own ID
+fn media_files(message: &Message) -> Option<(&'static str, Vec<String>)> {
+    if let Some(sticker) = message.sticker() {
+        return Some(("sticker", vec![sticker.file_unique_id.clone()]));
+    }
+    // GIFs are sent as documents too, so check them first
+    if let Some(animation) = message.animation() {
+        return Some(("GIF", vec![animation.file_unique_id.clone()]));
+    }
+    if let Some(photo) = message.photo() {
+        return Some((
+            "photo",
+            photo
+                .iter()
+                .map(|size| size.file_unique_id.clone())
+                .collect(),
+        ));
+    }
+    if let Some(video) = message.video() {
+        return Some(("video", vec![video.file_unique_id.clone()]));
+    }
+    if let Some(document) = message.document() {
+        return Some(("document", vec![document.file_unique_id.clone()]));
+    }
+
+    None
+}
+
+// delete media that's on the chat's or the global blocklist, returning true if the message was removed
+pub async fn handle_message(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<bool> {
+    if message.chat.is_private() {
+        return Ok(false);
+    }
+
+    let files = match media_files(message) {
+        Some((_, files)) => files,
+        None => return Ok(false),
+    };
+    let blocked = match media::find_blocked_media(message.chat.id, &files, pool).await? {
+        Some(blocked) => blocked,
+        None => return Ok(false),
+    };
+
+    // admins and approved users can post whatever they like
+    let sender_id = match utils::sender_id(message) {
+        Some(sender_id) => sender_id,
+        None => return Ok(false),
+    };
+    if perms::is_user_exempt(bot, message.chat.id, sender_id, pool).await? {
+        return Ok(false);
+    }
+
+    log::info!(
+        "Removing blocked {} {} from {} in chat {}",
+        blocked.media_type,
+        blocked.file_unique_id,
+        sender_id,
+        message.chat.id
+    );
+    bot.delete_message(message.chat.id, message.id).await.ok();
+
+    if let Some(action) = blocked
+        .action
+        .and_then(|action| action.parse::<FilterAction>().ok())
+    {
+        if banning::punish_user(bot, message.chat.id, sender_id, action).await? {
+            bot.send_message(
+                message.chat.id,
+                format!(
+                    "{} was {} for posting a blocked {}.",
+                    utils::sender_mention(message),
+                    action.past_tense(),
+                    blocked.media_type
+                ),
+            )
+            .await?;
+        }
+    }
+
+    Ok(true)
+}
+
+// split off the 'global' flag that makes a command act on the owner's blocklist
+fn parse_scope<'a>(args: &'a [&'a str]) -> (bool, &'a [&'a str]) {
+    match args {
+        ["global", rest @ ..] => (true, rest),
+        rest => (false, rest),
+    }
+}
+
+// the global blocklist is managed by the bot's owner, chat blocklists by the chat's admins
+async fn require_scope_perms(
+    bot: &crate::Bot,
+    message: &Message,
+    global: bool,
+) -> anyhow::Result<bool> {
+    if !global {
+        tokio::try_join!(
+            perms::require_group(bot, message), // command needs to be in a public group
+            perms::require_restrict_chat_members(bot, message), // user requires RESTRICT_CHAT_MEMBERS permissions
+            perms::require_bot_restrict_chat_members(bot, message), // bot requires RESTRICT_CHAT_MEMBERS permissions
+            perms::require_bot_can_delete_messages(bot, message), // bot requires CAN_DELETE_MESSAGES permissions
+        )?;
+        return Ok(true);
+    }
+
+    let is_owner =
+        matches!((message.from(), *OWNER_ID), (Some(user), Some(owner_id)) if user.id == owner_id);
+    if !is_owner {
+        bot.send_message(
+            message.chat.id,
+            "Only the bot's owner can manage the global blocklist.",
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+    }
+
+    Ok(is_owner)
+}
+
+pub async fn block_media(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let args: Vec<_> = message
+        .text()
+        .map(|text| text.split_whitespace().skip(1).collect())
+        .unwrap_or_default();
+    let (global, rest) = parse_scope(&args);
+
+    if !require_scope_perms(bot, message, global).await? {
+        return Ok(());
+    }
+
+    let action = match rest {
+        [] => None,
+        [action] => match action.parse::<FilterAction>() {
+            Ok(action) => Some(action),
+            Err(err) => {
+                bot.send_message(message.chat.id, err)
+                    .reply_to_message_id(message.id)
+                    .await?;
+                return Ok(());
+            }
+        },
+        _ => {
+            bot.send_message(
+                message.chat.id,
+                "Usage: reply to a photo, sticker, GIF or document with <code>/blockmedia [global] [ban|kick|mute]</code>",
+            )
+            .reply_to_message_id(message.id)
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let reply = match message.reply_to_message() {
+        Some(reply) => reply,
+        None => {
+            let (chat_count, global_count) =
+                media::count_blocked_media(message.chat.id, pool).await?;
+            bot.send_message(
+                message.chat.id,
+                format!(
+                    "{} file(s) are blocked in this chat and {} globally. Reply to a photo, sticker, GIF or document to block it.",
+                    chat_count, global_count
+                ),
+            )
+            .reply_to_message_id(message.id)
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let (media_type, files) = match media_files(reply) {
+        Some(media) => media,
+        None => {
+            bot.send_message(
+                message.chat.id,
+                "That message doesn't have any media I can block.",
+            )
+            .reply_to_message_id(message.id)
+            .await?;
+            return Ok(());
+        }
+    };
+    let file_unique_id = files.last().cloned().unwrap_or_default();
+
+    let chat_id = if global { None } else { Some(message.chat.id) };
+    media::block_media(
+        chat_id,
+        &BlockedMedia {
+            file_unique_id: file_unique_id.clone(),
+            media_type: media_type.to_owned(),
+            action: action.map(|action| action.to_string()),
+        },
+        pool,
+    )
+    .await?;
+
+    // the message that prompted this is the first to go
+    bot.delete_message(message.chat.id, reply.id).await.ok();
+
+    let mut text = format!(
+        "This {} will be deleted on sight {}",
+        media_type,
+        if global { "everywhere" } else { "in this chat" }
+    );
+    if let Some(action) = action {
+        text.push_str(&format!(" and its senders {}", action.past_tense()));
+    }
+    text.push_str(&format!(
+        ". File ID: {}",
+        html::code_inline(&file_unique_id)
+    ));
+
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn unblock_media(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let args: Vec<_> = message
+        .text()
+        .map(|text| text.split_whitespace().skip(1).collect())
+        .unwrap_or_default();
+    let (global, rest) = parse_scope(&args);
+
+    if !require_scope_perms(bot, message, global).await? {
+        return Ok(());
+    }
+
+    // media can be unblocked by replying to it, or by its file ID if it's already been deleted
+    let files = match (message.reply_to_message().and_then(media_files), rest) {
+        (Some((_, files)), []) => files,
+        (None, [file_unique_id]) => vec![file_unique_id.to_string()],
+        _ => {
+            bot.send_message(
+                message.chat.id,
+                "Usage: reply to blocked media with <code>/unblockmedia [global]</code>, or use <code>/unblockmedia [global] &lt;file ID&gt;</code>",
+            )
+            .reply_to_message_id(message.id)
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let chat_id = if global { None } else { Some(message.chat.id) };
+    let mut removed = false;
+    for file_unique_id in &files {
+        removed |= media::unblock_media(chat_id, file_unique_id, pool).await?;
+    }
+
+    let text = match (removed, global) {
+        (true, true) => "That media is no longer blocked globally.",
+        (true, false) => "That media is no longer blocked in this chat.",
+        (false, true) => "That media isn't on the global blocklist.",
+        (false, false) => "That media isn't blocked in this chat.",
+    };
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
@@ -1,9 +1,13 @@
+use chrono_tz::Tz;
 use sqlx::{Pool, Postgres};
 use teloxide::payloads::SendMessageSetters;
 use teloxide::types::{ChatKind, ForwardedFrom};
 use teloxide::{prelude2::*, utils::html};
 
-use crate::utils;
+use crate::{
+    repo::timezones,
+    utils::{self, perms},
+};
 
 pub async fn handle_id(
     bot: &crate::Bot,
@@ -56,3 +60,56 @@ pub async fn handle_id(
     }
     Ok(())
 }
+
+pub async fn set_timezone(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    // check for required conditions
+    tokio::try_join!(
+        perms::require_group_or_connection(bot, message), // needs a group or a connected PM
+        perms::require_user_admin(bot, message),          // user requires admin permissions
+    )?;
+
+    let chat_id = utils::target_chat(message, pool).await?;
+
+    match message
+        .text()
+        .and_then(|text| text.split_whitespace().nth(1))
+    {
+        Some(timezone) => match timezone.parse::<Tz>() {
+            Ok(tz) => {
+                timezones::set_timezone(chat_id, tz.name(), pool).await?;
+                bot.send_message(
+                    message.chat.id,
+                    format!("This chat's timezone is now {}.", html::escape(tz.name())),
+                )
+                .reply_to_message_id(message.id)
+                .await?;
+            }
+            Err(_) => {
+                bot.send_message(
+                    message.chat.id,
+                    "That's not a timezone I know of; try something like <code>Europe/Berlin</code> or <code>UTC</code>.",
+                )
+                .reply_to_message_id(message.id)
+                .await?;
+            }
+        },
+        None => {
+            let tz = utils::chat_timezone(chat_id, pool).await?;
+            bot.send_message(
+                message.chat.id,
+                format!(
+                    "This chat's timezone is {}. Use <code>/settimezone &lt;timezone&gt;</code> to change it, eg: <code>/settimezone Europe/Berlin</code>",
+                    html::escape(tz.name())
+                ),
+            )
+            .reply_to_message_id(message.id)
+            .await?;
+        }
+    }
+
+    Ok(())
+}
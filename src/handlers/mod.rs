@@ -1,15 +1,34 @@
 pub mod admin;
+pub mod admininfo;
+pub mod anonymous;
+pub mod antichannel;
+pub mod antidupe;
+pub mod antiraid;
+pub mod appeals;
+pub mod approvals;
 pub mod banning;
+pub mod connection;
+pub mod disabling;
 pub mod filters;
+pub mod linkfilter;
+pub mod linking;
+pub mod mediafilter;
 pub mod misc;
+pub mod moderators;
 pub mod muting;
+pub mod namefilter;
+pub mod nightmode;
+pub mod pruning;
+pub mod purging;
+pub mod reporting;
+pub mod scriptfilter;
 
 use sqlx::{Pool, Postgres};
 use teloxide::types::{ChatKind, Message};
 
 use crate::{
     entities::User,
-    repo::{chats, users},
+    repo::{activity, chats, users},
 };
 
 pub async fn save_user_handler(
@@ -51,3 +70,28 @@ pub async fn save_chat_handler(
 
     Ok(())
 }
+
+// returns true if this is the first time the sender spoke in the chat
+pub async fn save_activity_handler(
+    _bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<bool> {
+    // only track who speaks in groups
+    if message.chat.is_private() {
+        return Ok(false);
+    }
+
+    // whoever added new members didn't say anything, and the members themselves
+    // should still be checked when they first speak
+    if message.new_chat_members().is_some() {
+        return Ok(false);
+    }
+
+    match message.from() {
+        Some(user) if !user.is_bot => {
+            activity::set_last_seen(message.chat.id, user.id, message.date, pool).await
+        }
+        _ => Ok(false),
+    }
+}
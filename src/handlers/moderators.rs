@@ -0,0 +1,185 @@
+use sqlx::{Pool, Postgres};
+use teloxide::{payloads::SendMessageSetters, prelude2::*, utils::html};
+
+use crate::{
+    repo::roles,
+    utils::{self, perms, Role},
+    BOT_ID,
+};
+
+pub async fn add_mod(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    // check for required conditions
+    tokio::try_join!(
+        perms::require_group_or_connection(bot, message), // needs a group or a connected PM
+        perms::require_promote_chat_members(bot, message), // user requires CAN_PROMOTE_MEMBERS permissions
+    )?;
+
+    let chat_id = utils::target_chat(message, pool).await?;
+
+    // extract user and role from message
+    let (user_id, args) = utils::extract_user_and_text(bot, message, pool).await;
+    let user_id = match user_id {
+        Some(id) => id,
+        None => {
+            bot.send_message(message.chat.id, "Try targeting a user next time bud.")
+                .reply_to_message_id(message.id)
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let role = match args.map(|role| role.trim().to_lowercase()) {
+        Some(role) if !role.is_empty() => match role.parse::<Role>() {
+            Ok(role) => role,
+            Err(err) => {
+                bot.send_message(message.chat.id, err)
+                    .reply_to_message_id(message.id)
+                    .await?;
+                return Ok(());
+            }
+        },
+        _ => Role::Moderator,
+    };
+
+    // user is a dumbass
+    if user_id == *BOT_ID {
+        bot.send_message(message.chat.id, "No u")
+            .reply_to_message_id(message.id)
+            .await?;
+        return Ok(());
+    }
+
+    if utils::is_channel(user_id) {
+        bot.send_message(message.chat.id, "Channels can't moderate chats.")
+            .reply_to_message_id(message.id)
+            .await?;
+        return Ok(());
+    }
+
+    // check if user is valid
+    let member = match bot.get_chat_member(chat_id, user_id).await {
+        Ok(member) => member,
+        Err(_) => {
+            bot.send_message(message.chat.id, "This user is ded mate.")
+                .reply_to_message_id(message.id)
+                .await?;
+            return Ok(());
+        }
+    };
+
+    // admins already have their telegram rights
+    if perms::is_user_admin(bot, message, user_id).await.is_ok() {
+        bot.send_message(
+            message.chat.id,
+            "This user is an admin, they don't need a role.",
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(());
+    }
+
+    roles::set_role(chat_id, user_id, &role.to_string(), pool).await?;
+
+    let commands: Vec<_> = role
+        .commands()
+        .iter()
+        .map(|command| format!("/{}", command))
+        .collect();
+    bot.send_message(
+        message.chat.id,
+        format!(
+            "{} is now a {} in this chat and can use: {}",
+            html::user_mention(user_id, &html::escape(&member.user.full_name())),
+            role,
+            commands.join(", ")
+        ),
+    )
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn remove_mod(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    // check for required conditions
+    tokio::try_join!(
+        perms::require_group_or_connection(bot, message), // needs a group or a connected PM
+        perms::require_promote_chat_members(bot, message), // user requires CAN_PROMOTE_MEMBERS permissions
+    )?;
+
+    let chat_id = utils::target_chat(message, pool).await?;
+
+    // extract user from message
+    let (user_id, _) = utils::extract_user_and_text(bot, message, pool).await;
+    let user_id = match user_id {
+        Some(id) => id,
+        None => {
+            bot.send_message(message.chat.id, "Try targeting a user next time bud.")
+                .reply_to_message_id(message.id)
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let text = if roles::remove_role(chat_id, user_id, pool).await? {
+        "Done, they no longer have a role in this chat."
+    } else {
+        "This user doesn't have a role in this chat."
+    };
+
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn list_mods(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    perms::require_group_or_connection(bot, message).await?;
+
+    let chat_id = utils::target_chat(message, pool).await?;
+
+    let mods = roles::get_roles(chat_id, pool).await?;
+    if mods.is_empty() {
+        bot.send_message(message.chat.id, "Nobody has a role in this chat.")
+            .reply_to_message_id(message.id)
+            .await?;
+        return Ok(());
+    }
+
+    let list: Vec<_> = mods
+        .iter()
+        .map(|(user_id, role, full_name)| {
+            format!(
+                "- {} ({}): {}",
+                html::user_mention(
+                    *user_id,
+                    &html::escape(full_name.as_deref().unwrap_or("Unknown user"))
+                ),
+                html::code_inline(&user_id.to_string()),
+                html::escape(role)
+            )
+        })
+        .collect();
+
+    bot.send_message(
+        message.chat.id,
+        format!("Roles in this chat:\n{}", list.join("\n")),
+    )
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(())
+}
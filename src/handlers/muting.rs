@@ -1,17 +1,72 @@
-use std::convert::TryInto;
-
-use chrono::Duration;
+use chrono::{DateTime, Utc};
 use sqlx::{Pool, Postgres};
 use teloxide::{
     payloads::SendMessageSetters,
     prelude2::*,
-    types::{ChatMember, ChatMemberStatus, ChatPermissions},
+    types::{ChatMember, ChatMemberKind, ChatMemberStatus, ChatPermissions, UntilDate},
+    utils::html,
+};
+
+use crate::{
+    handlers::{approvals, banning, linking},
+    repo::restrictions,
+    utils::{self, perms},
 };
+use crate::{utils::TimeSpan, BOT_ID};
+
+// take away a user's ability to speak, optionally only until the specified time
+pub async fn mute_user(
+    bot: &crate::Bot,
+    chat_id: i64,
+    user_id: i64,
+    until_date: Option<DateTime<Utc>>,
+) -> anyhow::Result<()> {
+    let permissions = ChatPermissions::empty();
 
-use anyhow::anyhow;
+    match until_date {
+        Some(until_date) => {
+            bot.restrict_chat_member(chat_id, user_id, permissions)
+                .until_date(until_date)
+                .await?
+        }
+        None => {
+            bot.restrict_chat_member(chat_id, user_id, permissions)
+                .await?
+        }
+    };
 
-use crate::utils::{self, perms};
-use crate::{utils::UnitOfTime, BOT_ID};
+    Ok(())
+}
+
+// mute users in the chats linked with the current one as well
+pub async fn mute_in_linked_chats(
+    bot: &crate::Bot,
+    message: &Message,
+    user_ids: &[i64],
+    until_date: Option<DateTime<Utc>>,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    linking::apply_to_linked(
+        bot,
+        message,
+        "Muted",
+        user_ids,
+        |chat_id, user_id| async move {
+            let chat_member = linking::check_linked_target(bot, chat_id, user_id, pool).await?;
+            if matches!(
+                chat_member.status(),
+                ChatMemberStatus::Banned | ChatMemberStatus::Left
+            ) {
+                return Err("they aren't in that chat.".to_owned());
+            }
+            utils::retry_rate_limited(|| mute_user(bot, chat_id, user_id, until_date))
+                .await
+                .map_err(|err| html::escape(&err.to_string()))
+        },
+        pool,
+    )
+    .await
+}
 
 pub async fn mute(
     bot: &crate::Bot,
@@ -28,8 +83,49 @@ pub async fn mute(
         perms::require_bot_restrict_chat_members(bot, message) // bot requires RESTRICT_CHAT_MEMBERS permissions
     )?;
 
-    // extract user and text from message
-    let (user_id, args) = utils::extract_user_and_text(bot, message, pool).await;
+    // several users can be muted at once
+    let (mut targets, args) = utils::extract_users_and_text(message, pool).await;
+    if targets.len() > 1 {
+        let until_date = match is_tmute {
+            true => match banning::parse_until(bot, message, args, pool).await? {
+                Some((until_date, _)) => Some(until_date),
+                None => return Ok(()),
+            },
+            false => None,
+        };
+
+        let chat_id = chat.id;
+        let (summary, muted) = utils::mass_action(targets, "Muted", |user_id| async move {
+            let chat_member = banning::check_mass_target(bot, chat_id, user_id, pool).await?;
+            if matches!(
+                chat_member.status(),
+                ChatMemberStatus::Banned | ChatMemberStatus::Left
+            ) {
+                return Err("This user isn't in the chat!".to_owned());
+            }
+            utils::retry_rate_limited(|| mute_user(bot, chat_id, user_id, until_date))
+                .await
+                .map_err(|err| html::escape(&err.to_string()))
+        })
+        .await;
+
+        bot.send_message(chat_id, summary)
+            .reply_to_message_id(message.id)
+            .await?;
+        return mute_in_linked_chats(bot, message, &muted, until_date, pool).await;
+    }
+
+    // a single target was already resolved above
+    let user_id = match targets.pop() {
+        Some(Ok(user_id)) => Some(user_id),
+        Some(Err(reason)) => {
+            bot.send_message(message.chat.id, reason)
+                .reply_to_message_id(message.id)
+                .await?;
+            return Ok(());
+        }
+        None => None,
+    };
     if user_id.is_none() {
         // no user was targeted
         bot.send_message(message.chat.id, "Try targeting a user next time bud.")
@@ -42,13 +138,21 @@ pub async fn mute(
     if args.is_none() && is_tmute {
         bot.send_message(
             message.chat.id,
-            "You need to specify a duration in d/h/m/s (days, hours, minutes, seconds)",
+            "You need to specify a duration or end time, eg: 1d12h or until friday 9am",
         )
         .reply_to_message_id(message.id)
         .await?;
         return Ok(());
     }
 
+    // channels can't be muted
+    if utils::is_channel(user_id.unwrap()) {
+        bot.send_message(message.chat.id, "Channels can't be muted, only banned.")
+            .reply_to_message_id(message.id)
+            .await?;
+        return Ok(());
+    }
+
     // check if user is valid
     let chat_member: ChatMember = match bot.get_chat_member(chat.id, user_id.unwrap()).await {
         Ok(m) => m, // user is valid
@@ -88,51 +192,56 @@ pub async fn mute(
     // check if user is already restricted
     let is_restricted = perms::is_user_restricted(bot, message, user_id.unwrap()).await?;
 
-    let permissions = ChatPermissions::empty();
-
-    if let Some(args) = args {
-        if is_tmute {
-            // get unit of time
-            let unit = args.parse::<UnitOfTime>();
-            if unit.is_err() {
-                bot.send_message(message.chat.id,"failed to get specified time; expected one of d/h/m/s (days, hours, minutes, seconds)")
-								.reply_to_message_id(message.id)
-								.await?;
-                return Ok(());
-            }
+    if is_tmute {
+        // get when the mute should end
+        let tz = utils::chat_timezone(chat.id, pool).await?;
+        let (until_time, until_desc) =
+            match utils::parse_end_time(&args.unwrap_or_default(), message.date, tz) {
+                Ok(end) => end,
+                Err(err) => {
+                    bot.send_message(message.chat.id, err)
+                        .reply_to_message_id(message.id)
+                        .await?;
+                    return Ok(());
+                }
+            };
 
-            // convert to seconds
-            let time = utils::extract_time(unit.as_ref().unwrap());
-            let until_time = message
-                .date
-                .checked_add_signed(Duration::seconds(time.try_into().unwrap()))
-                .ok_or(anyhow!("Something went wrong!"))?;
+        // approved users need confirmation
+        if perms::is_user_approved(chat.id, user_id.unwrap(), pool).await? {
+            return approvals::confirm_action(
+                bot,
+                message,
+                "mute",
+                user_id.unwrap(),
+                Some(until_time),
+            )
+            .await;
+        }
 
-            // mute chat member for specified time
-            bot.restrict_chat_member(chat.id, user_id.unwrap(), permissions)
-                .until_date(until_time)
-                .await?;
+        // mute chat member for specified time
+        mute_user(bot, chat.id, user_id.unwrap(), Some(until_time)).await?;
 
-            if is_restricted {
-                bot.send_message(
-                    message.chat.id,
-                    format!(
-                        "Restrictions have been updated. Muted for {}!",
-                        unit.unwrap()
-                    ),
-                )
+        if is_restricted {
+            bot.send_message(
+                message.chat.id,
+                format!("Restrictions have been updated. Muted {}!", until_desc),
+            )
+            .reply_to_message_id(message.id)
+            .await?;
+        } else {
+            bot.send_message(message.chat.id, format!("Muted {}!", until_desc))
                 .reply_to_message_id(message.id)
                 .await?;
-            } else {
-                bot.send_message(message.chat.id, format!("Muted for {}!", unit.unwrap()))
-                    .reply_to_message_id(message.id)
-                    .await?;
-            }
         }
+        mute_in_linked_chats(bot, message, &[user_id.unwrap()], Some(until_time), pool).await?;
     } else {
+        // approved users need confirmation
+        if perms::is_user_approved(chat.id, user_id.unwrap(), pool).await? {
+            return approvals::confirm_action(bot, message, "mute", user_id.unwrap(), None).await;
+        }
+
         // permanently mute chat member
-        bot.restrict_chat_member(chat.id, user_id.unwrap(), permissions)
-            .await?;
+        mute_user(bot, chat.id, user_id.unwrap(), None).await?;
 
         if is_restricted {
             bot.send_message(
@@ -146,6 +255,7 @@ pub async fn mute(
                 .reply_to_message_id(message.id)
                 .await?;
         }
+        mute_in_linked_chats(bot, message, &[user_id.unwrap()], None, pool).await?;
     }
 
     Ok(())
@@ -221,6 +331,7 @@ pub async fn unmute(
     // unmute the user
     bot.restrict_chat_member(chat.id, user_id.unwrap(), permissions)
         .await?;
+    restrictions::remove_restrictions(chat.id, user_id.unwrap(), pool).await?;
 
     // let user know something happened
     bot.send_message(message.chat.id, "Unmuted!")
@@ -229,3 +340,352 @@ pub async fn unmute(
 
     Ok(())
 }
+
+// map a named restriction to the permissions it grants back and the ones it takes away;
+// dependent permissions (eg: stickers on media) follow automatically
+fn parse_restriction(name: &str) -> Option<(ChatPermissions, ChatPermissions)> {
+    Some(match name {
+        "media" => (
+            ChatPermissions::SEND_MEDIA_MESSAGES,
+            ChatPermissions::SEND_MEDIA_MESSAGES - ChatPermissions::SEND_MESSAGES,
+        ),
+        "stickers" => (
+            ChatPermissions::SEND_OTHER_MESSAGES,
+            ChatPermissions::SEND_OTHER_MESSAGES - ChatPermissions::SEND_MEDIA_MESSAGES,
+        ),
+        // telegram can only take away link previews, the links themselves still go through
+        "links" | "previews" => (
+            ChatPermissions::ADD_WEB_PAGE_PREVIEWS,
+            ChatPermissions::ADD_WEB_PAGE_PREVIEWS - ChatPermissions::SEND_MEDIA_MESSAGES,
+        ),
+        "polls" => (
+            ChatPermissions::SEND_POLLS,
+            ChatPermissions::SEND_POLLS - ChatPermissions::SEND_MESSAGES,
+        ),
+        "invite" => (ChatPermissions::INVITE_USERS, ChatPermissions::INVITE_USERS),
+        "pin" => (ChatPermissions::PIN_MESSAGES, ChatPermissions::PIN_MESSAGES),
+        "all" => (ChatPermissions::all(), ChatPermissions::all()),
+        _ => return None,
+    })
+}
+
+// get the permissions a user currently has revoked; polls, invites and pins aren't
+// reported by telegram, so the ones we took away are kept track of ourselves
+async fn current_restrictions(
+    chat_id: i64,
+    chat_member: &ChatMember,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<ChatPermissions> {
+    let restricted = match &chat_member.kind {
+        ChatMemberKind::Restricted(restricted) => restricted,
+        // whatever we saved before no longer applies
+        _ => return Ok(ChatPermissions::empty()),
+    };
+
+    let mut revoked = restrictions::get_revoked_permissions(chat_id, chat_member.user.id, pool)
+        .await?
+        .map(|bits| ChatPermissions::from_bits_truncate(bits as u8))
+        .unwrap_or_else(ChatPermissions::empty);
+
+    let existing = [
+        (restricted.can_send_messages, ChatPermissions::SEND_MESSAGES),
+        (
+            restricted.can_send_media_messages,
+            ChatPermissions::SEND_MEDIA_MESSAGES - ChatPermissions::SEND_MESSAGES,
+        ),
+        (
+            restricted.can_send_other_messages,
+            ChatPermissions::SEND_OTHER_MESSAGES - ChatPermissions::SEND_MEDIA_MESSAGES,
+        ),
+        (
+            restricted.can_add_web_page_previews,
+            ChatPermissions::ADD_WEB_PAGE_PREVIEWS - ChatPermissions::SEND_MEDIA_MESSAGES,
+        ),
+    ];
+
+    for (allowed, permission) in existing {
+        if !allowed {
+            revoked |= permission;
+        }
+    }
+
+    Ok(revoked)
+}
+
+// get when a user's current restrictions run out, if they ever do
+fn restricted_until(chat_member: &ChatMember) -> Option<DateTime<Utc>> {
+    match &chat_member.kind {
+        ChatMemberKind::Restricted(restricted) => match restricted.until_date {
+            UntilDate::Date(until_date) => Some(until_date),
+            UntilDate::Forever => None,
+        },
+        _ => None,
+    }
+}
+
+// apply a set of revoked permissions on top of the chat's defaults and remember them
+async fn apply_restrictions(
+    bot: &crate::Bot,
+    chat_id: i64,
+    user_id: i64,
+    revoked: ChatPermissions,
+    until_date: Option<DateTime<Utc>>,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let permissions = bot
+        .get_chat(chat_id)
+        .await?
+        .permissions()
+        .unwrap_or_else(ChatPermissions::all)
+        - revoked;
+
+    match until_date {
+        Some(until_date) => {
+            bot.restrict_chat_member(chat_id, user_id, permissions)
+                .until_date(until_date)
+                .await?
+        }
+        None => {
+            bot.restrict_chat_member(chat_id, user_id, permissions)
+                .await?
+        }
+    };
+
+    if revoked.is_empty() {
+        restrictions::remove_restrictions(chat_id, user_id, pool).await?;
+    } else {
+        restrictions::set_revoked_permissions(chat_id, user_id, revoked.bits() as i16, pool)
+            .await?;
+    }
+
+    Ok(())
+}
+
+pub async fn restrict(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let chat = &message.chat;
+
+    // check for required conditions
+    tokio::try_join!(
+        perms::require_group(bot, message), // command needs to be in a public group
+        perms::require_restrict_chat_members(bot, message), // user requires RESTRICT_CHAT_MEMBERS permissions
+        perms::require_bot_restrict_chat_members(bot, message) // bot requires RESTRICT_CHAT_MEMBERS permissions
+    )?;
+
+    // extract user and text from message
+    let (user_id, args) = utils::extract_user_and_text(bot, message, pool).await;
+    if user_id.is_none() {
+        // no user was targeted
+        bot.send_message(message.chat.id, "Try targeting a user next time bud.")
+            .reply_to_message_id(message.id)
+            .await?;
+        return Ok(());
+    }
+
+    let args = args.unwrap_or_default().to_lowercase();
+    let mut names: Vec<_> = args
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|s| !s.is_empty())
+        .collect();
+
+    // a trailing duration makes the restriction temporary
+    let span = match names.last().map(|last| last.parse::<TimeSpan>()) {
+        Some(Ok(span)) => {
+            names.pop();
+            Some(span)
+        }
+        // looks like a duration, but isn't one we can use
+        Some(Err(err))
+            if names
+                .last()
+                .map_or(false, |last| last.starts_with(|c: char| c.is_ascii_digit())) =>
+        {
+            bot.send_message(message.chat.id, err)
+                .reply_to_message_id(message.id)
+                .await?;
+            return Ok(());
+        }
+        _ => None,
+    };
+
+    let mut revoked = ChatPermissions::empty();
+    for name in &names {
+        match parse_restriction(name) {
+            Some((_, permissions)) => revoked |= permissions,
+            None => {
+                bot.send_message(
+                    message.chat.id,
+                    "You need to specify what to restrict: media, stickers, links, polls, invite, pin or all",
+                )
+                .reply_to_message_id(message.id)
+                .await?;
+                return Ok(());
+            }
+        }
+    }
+    if revoked.is_empty() {
+        bot.send_message(
+            message.chat.id,
+            "You need to specify what to restrict: media, stickers, links, polls, invite, pin or all",
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(());
+    }
+
+    // check if user is valid
+    let chat_member: ChatMember = match bot.get_chat_member(chat.id, user_id.unwrap()).await {
+        Ok(m) => m, // user is valid
+        Err(_) => {
+            bot.send_message(message.chat.id, "This user is ded mate.")
+                .reply_to_message_id(message.id)
+                .await?; // invalid user (outdated info in db?)
+            return Ok(());
+        }
+    };
+
+    match chat_member.status() {
+        // don't try to restrict admins
+        ChatMemberStatus::Administrator | ChatMemberStatus::Owner => {
+            bot.send_message(message.chat.id, "I'm not restricting an administrator!")
+                .reply_to_message_id(message.id)
+                .await?;
+            return Ok(());
+        }
+
+        // don't try to restrict users not in the chat
+        ChatMemberStatus::Banned | ChatMemberStatus::Left => {
+            bot.send_message(message.chat.id, "This user isn't in the chat!")
+                .reply_to_message_id(message.id)
+                .await?;
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    // user is a dumbass
+    if user_id.unwrap() == *BOT_ID {
+        bot.send_message(message.chat.id, "No u")
+            .reply_to_message_id(message.id)
+            .await?;
+        return Ok(());
+    }
+
+    // merge with whatever restrictions the user already has
+    let revoked = current_restrictions(chat.id, &chat_member, pool).await? | revoked;
+
+    if let Some(span) = span {
+        // restrict chat member for specified time
+        apply_restrictions(
+            bot,
+            chat.id,
+            user_id.unwrap(),
+            revoked,
+            Some(span.until(message.date)),
+            pool,
+        )
+        .await?;
+
+        bot.send_message(
+            message.chat.id,
+            format!("Restricted {} for {}!", names.join(", "), span),
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+    } else {
+        // without a duration an existing time limit is kept, otherwise it's permanent
+        let until_date = restricted_until(&chat_member);
+        apply_restrictions(bot, chat.id, user_id.unwrap(), revoked, until_date, pool).await?;
+
+        bot.send_message(message.chat.id, format!("Restricted {}!", names.join(", ")))
+            .reply_to_message_id(message.id)
+            .await?;
+    }
+
+    Ok(())
+}
+
+pub async fn unrestrict(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let chat = &message.chat;
+
+    // check for required conditions
+    tokio::try_join!(
+        perms::require_group(bot, message), // command needs to be in a public group
+        perms::require_restrict_chat_members(bot, message), // user requires RESTRICT_CHAT_MEMBERS permissions
+        perms::require_bot_restrict_chat_members(bot, message) // bot requires RESTRICT_CHAT_MEMBERS permissions
+    )?;
+
+    // extract user and text from message
+    let (user_id, args) = utils::extract_user_and_text(bot, message, pool).await;
+    if user_id.is_none() {
+        // no user was targeted
+        bot.send_message(message.chat.id, "Try targeting a user next time bud.")
+            .reply_to_message_id(message.id)
+            .await?;
+        return Ok(());
+    }
+
+    let args = args.unwrap_or_default().to_lowercase();
+    let names: Vec<_> = args
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|s| !s.is_empty())
+        .collect();
+
+    // check if user is valid
+    let chat_member: ChatMember = match bot.get_chat_member(chat.id, user_id.unwrap()).await {
+        Ok(m) => m, // user is valid
+        Err(_) => {
+            bot.send_message(message.chat.id, "This user is ded mate.")
+                .reply_to_message_id(message.id)
+                .await?; // invalid user
+            return Ok(());
+        }
+    };
+
+    // nothing to lift for users that aren't restricted
+    if !matches!(chat_member.status(), ChatMemberStatus::Restricted) {
+        bot.send_message(message.chat.id, "This user isn't restricted!")
+            .reply_to_message_id(message.id)
+            .await?;
+        return Ok(());
+    }
+
+    // without arguments everything is lifted
+    let mut granted = if names.is_empty() {
+        ChatPermissions::all()
+    } else {
+        ChatPermissions::empty()
+    };
+    for name in &names {
+        match parse_restriction(name) {
+            Some((permissions, _)) => granted |= permissions,
+            None => {
+                bot.send_message(
+                    message.chat.id,
+                    "You can lift restrictions on: media, stickers, links, polls, invite, pin or all",
+                )
+                .reply_to_message_id(message.id)
+                .await?;
+                return Ok(());
+            }
+        }
+    }
+
+    // restrictions that aren't lifted keep their time limit; the chat's defaults still apply
+    let revoked = current_restrictions(chat.id, &chat_member, pool).await? - granted;
+    let until_date = restricted_until(&chat_member);
+    apply_restrictions(bot, chat.id, user_id.unwrap(), revoked, until_date, pool).await?;
+
+    bot.send_message(message.chat.id, "Restrictions lifted!")
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
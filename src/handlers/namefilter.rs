@@ -0,0 +1,301 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use lazy_static::lazy_static;
+use regex::{Regex, RegexBuilder};
+use sqlx::{Pool, Postgres};
+use teloxide::{payloads::SendMessageSetters, prelude2::*, types::User, utils::html};
+
+use crate::{
+    handlers::banning,
+    repo::namefilters,
+    utils::{self, perms, FilterAction},
+    BOT_ID,
+};
+
+// keep user supplied patterns from blowing up
+const REGEX_SIZE_LIMIT: usize = 1 << 16;
+
+// a name filter ready to be matched against
+struct CompiledFilter {
+    pattern: String,
+    regex: Regex,
+    action: FilterAction,
+}
+
+lazy_static! {
+    // compiled name filters per chat, dropped whenever a chat's filters change
+    static ref COMPILED_FILTERS: Mutex<HashMap<i64, Arc<Vec<CompiledFilter>>>> =
+        Mutex::new(HashMap::new());
+}
+
+fn build_regex(pattern: &str) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .size_limit(REGEX_SIZE_LIMIT)
+        .build()
+}
+
+// get a chat's name filters, compiling them only the first time they're needed
+async fn compiled_filters(
+    chat_id: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<Arc<Vec<CompiledFilter>>> {
+    if let Some(filters) = COMPILED_FILTERS.lock().unwrap().get(&chat_id) {
+        return Ok(filters.clone());
+    }
+
+    let filters: Vec<_> = namefilters::get_name_filters(chat_id, pool)
+        .await?
+        .into_iter()
+        .filter_map(|filter| {
+            Some(CompiledFilter {
+                regex: build_regex(&filter.pattern).ok()?,
+                action: filter.action.parse::<FilterAction>().ok()?,
+                pattern: filter.pattern,
+            })
+        })
+        .collect();
+
+    let filters = Arc::new(filters);
+    COMPILED_FILTERS
+        .lock()
+        .unwrap()
+        .insert(chat_id, filters.clone());
+    Ok(filters)
+}
+
+fn invalidate_filters(chat_id: i64) {
+    COMPILED_FILTERS.lock().unwrap().remove(&chat_id);
+}
+
+// find the first filter matching the user's display name or username
+fn find_match<'a>(filters: &'a [CompiledFilter], user: &User) -> Option<&'a CompiledFilter> {
+    let full_name = user.full_name();
+
+    filters.iter().find(|filter| {
+        filter.regex.is_match(&full_name)
+            || user
+                .username
+                .as_deref()
+                .map_or(false, |username| filter.regex.is_match(username))
+    })
+}
+
+async fn check_user(
+    bot: &crate::Bot,
+    message: &Message,
+    user: &User,
+    filters: &[CompiledFilter],
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let chat_id = message.chat.id;
+    if user.id == *BOT_ID {
+        return Ok(());
+    }
+
+    let filter = match find_match(filters, user) {
+        Some(filter) => filter,
+        None => return Ok(()),
+    };
+
+    // admins and approved users are left alone
+    if perms::is_user_exempt(bot, chat_id, user.id, pool).await? {
+        return Ok(());
+    }
+
+    log::info!(
+        "User {} in chat {} matched name filter {:?}, applying {}",
+        user.id,
+        chat_id,
+        filter.pattern,
+        filter.action
+    );
+    banning::punish_user(bot, chat_id, user.id, filter.action).await?;
+
+    bot.send_message(
+        chat_id,
+        format!(
+            "{} ({}) matched the name filter {} and was {}.",
+            html::user_mention(user.id, &html::escape(&user.full_name())),
+            html::code_inline(&user.id.to_string()),
+            html::code_inline(&filter.pattern),
+            filter.action.past_tense()
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+// check the names of users joining the chat
+pub async fn handle_joins(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let members = match message.new_chat_members() {
+        Some(members) => members,
+        None => return Ok(()),
+    };
+
+    let filters = compiled_filters(message.chat.id, pool).await?;
+    if filters.is_empty() {
+        return Ok(());
+    }
+
+    for member in members {
+        if let Err(err) = check_user(bot, message, member, &filters, pool).await {
+            log::error!(
+                "Failed to apply name filter to {} in {}: {}",
+                member.id,
+                message.chat.id,
+                err
+            );
+        }
+    }
+
+    Ok(())
+}
+
+// check the name of a user speaking in the chat for the first time
+pub async fn check_sender(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let user = match message.from() {
+        Some(user) => user,
+        None => return Ok(()),
+    };
+
+    let filters = compiled_filters(message.chat.id, pool).await?;
+    if filters.is_empty() {
+        return Ok(());
+    }
+
+    check_user(bot, message, user, &filters, pool).await
+}
+
+pub async fn name_filter(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    // check for required conditions
+    tokio::try_join!(
+        perms::require_group_or_connection(bot, message), // needs a group or a connected PM
+        perms::require_restrict_chat_members(bot, message), // user requires RESTRICT_CHAT_MEMBERS permissions
+        perms::require_bot_restrict_chat_members(bot, message) // bot requires RESTRICT_CHAT_MEMBERS permissions
+    )?;
+
+    let chat_id = utils::target_chat(message, pool).await?;
+
+    let args = message
+        .text()
+        .and_then(|text| text.split_once(char::is_whitespace))
+        .map(|(_, args)| args.trim())
+        .unwrap_or_default();
+    let (subcommand, rest) = args
+        .split_once(char::is_whitespace)
+        .map(|(subcommand, rest)| (subcommand, rest.trim()))
+        .unwrap_or((args, ""));
+
+    match subcommand {
+        "" | "list" => {
+            let filters = namefilters::get_name_filters(chat_id, pool).await?;
+            let text = if filters.is_empty() {
+                "No name filters are set up in this chat.".to_owned()
+            } else {
+                let list: Vec<_> = filters
+                    .iter()
+                    .map(|filter| {
+                        format!(
+                            "- {} ({})",
+                            html::code_inline(&filter.pattern),
+                            html::escape(&filter.action)
+                        )
+                    })
+                    .collect();
+                format!("Name filters in this chat:\n{}", list.join("\n"))
+            };
+
+            bot.send_message(message.chat.id, text)
+                .reply_to_message_id(message.id)
+                .await?;
+        }
+        "add" => {
+            // the action is the last word, the pattern is everything before it
+            let (pattern, action) = match rest.rsplit_once(char::is_whitespace) {
+                Some((pattern, action)) => (pattern.trim(), action.parse::<FilterAction>()),
+                None => {
+                    bot.send_message(
+                        message.chat.id,
+                        "Usage: <code>/namefilter add &lt;regex&gt; &lt;ban|kick|mute&gt;</code>",
+                    )
+                    .reply_to_message_id(message.id)
+                    .await?;
+                    return Ok(());
+                }
+            };
+            let action = match action {
+                Ok(action) => action,
+                Err(err) => {
+                    bot.send_message(message.chat.id, err)
+                        .reply_to_message_id(message.id)
+                        .await?;
+                    return Ok(());
+                }
+            };
+            if let Err(err) = build_regex(pattern) {
+                bot.send_message(
+                    message.chat.id,
+                    format!(
+                        "That's not a valid pattern:\n{}",
+                        html::code_block(&err.to_string())
+                    ),
+                )
+                .reply_to_message_id(message.id)
+                .await?;
+                return Ok(());
+            }
+
+            namefilters::add_name_filter(chat_id, pattern, &action.to_string(), pool).await?;
+            invalidate_filters(chat_id);
+            bot.send_message(
+                message.chat.id,
+                format!(
+                    "Users whose name matches {} will be {}.",
+                    html::code_inline(pattern),
+                    action.past_tense()
+                ),
+            )
+            .reply_to_message_id(message.id)
+            .await?;
+        }
+        "remove" | "rm" | "del" => {
+            let text = if namefilters::remove_name_filter(chat_id, rest, pool).await? {
+                invalidate_filters(chat_id);
+                format!("Removed the name filter {}.", html::code_inline(rest))
+            } else {
+                "There's no name filter with that pattern.".to_owned()
+            };
+
+            bot.send_message(message.chat.id, text)
+                .reply_to_message_id(message.id)
+                .await?;
+        }
+        _ => {
+            bot.send_message(
+                message.chat.id,
+                "Usage: <code>/namefilter add &lt;regex&gt; &lt;ban|kick|mute&gt;</code>, <code>/namefilter remove &lt;regex&gt;</code> or <code>/namefilter list</code>",
+            )
+            .reply_to_message_id(message.id)
+            .await?;
+        }
+    }
+
+    Ok(())
+}
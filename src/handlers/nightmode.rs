@@ -0,0 +1,288 @@
+use std::time::Duration;
+
+use chrono::{NaiveTime, Utc};
+use chrono_tz::Tz;
+use sqlx::{Pool, Postgres};
+use teloxide::{payloads::SendMessageSetters, prelude2::*, types::ChatPermissions, utils::html};
+
+use crate::{
+    entities::NightMode,
+    repo::nightmode,
+    utils::{self, perms},
+};
+
+// how often chats are checked for night mode transitions
+const SCHEDULER_INTERVAL: Duration = Duration::from_secs(60);
+
+// permissions to restore if the chat didn't report any when night mode started
+fn default_permissions() -> ChatPermissions {
+    ChatPermissions::empty()
+        | ChatPermissions::SEND_MESSAGES
+        | ChatPermissions::SEND_MEDIA_MESSAGES
+        | ChatPermissions::SEND_OTHER_MESSAGES
+        | ChatPermissions::SEND_POLLS
+        | ChatPermissions::ADD_WEB_PAGE_PREVIEWS
+}
+
+// check if a local time falls within the night, which may wrap around midnight
+fn is_night(start: NaiveTime, end: NaiveTime, now: NaiveTime) -> bool {
+    if start <= end {
+        start <= now && now < end
+    } else {
+        now >= start || now < end
+    }
+}
+
+async fn start_night(
+    bot: &crate::Bot,
+    night_mode: &NightMode,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    // save the current permissions so they can be restored in the morning
+    let chat = bot.get_chat(night_mode.chat_id).await?;
+    let saved_permissions = chat.permissions().unwrap_or_else(default_permissions);
+
+    bot.set_chat_permissions(night_mode.chat_id, ChatPermissions::empty())
+        .await?;
+    nightmode::set_night_mode_active(
+        night_mode.chat_id,
+        true,
+        Some(saved_permissions.bits() as i16),
+        pool,
+    )
+    .await?;
+
+    bot.send_message(
+        night_mode.chat_id,
+        format!(
+            "Night mode is on! The chat is locked until {} ({}).",
+            night_mode.end_time.format("%H:%M"),
+            html::escape(&night_mode.timezone)
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+async fn end_night(
+    bot: &crate::Bot,
+    night_mode: &NightMode,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let permissions = night_mode
+        .saved_permissions
+        .map(|bits| ChatPermissions::from_bits_truncate(bits as u8))
+        .unwrap_or_else(default_permissions);
+
+    bot.set_chat_permissions(night_mode.chat_id, permissions)
+        .await?;
+    nightmode::set_night_mode_active(night_mode.chat_id, false, None, pool).await?;
+
+    bot.send_message(
+        night_mode.chat_id,
+        "Good morning! Night mode is off, the chat is open again.",
+    )
+    .await?;
+
+    Ok(())
+}
+
+async fn check_night_modes(bot: &crate::Bot, pool: &Pool<Postgres>) -> anyhow::Result<()> {
+    for night_mode in nightmode::get_all_night_modes(pool).await? {
+        let tz = match night_mode.timezone.parse::<Tz>() {
+            Ok(tz) => tz,
+            Err(_) => continue,
+        };
+
+        let now = Utc::now().with_timezone(&tz).time();
+        let res = match (
+            is_night(night_mode.start_time, night_mode.end_time, now),
+            night_mode.active,
+        ) {
+            (true, false) => start_night(bot, &night_mode, pool).await,
+            (false, true) => end_night(bot, &night_mode, pool).await,
+            _ => Ok(()),
+        };
+
+        // one broken chat shouldn't stop the others from transitioning
+        if let Err(err) = res {
+            log::error!(
+                "Failed to update night mode for chat {}: {}",
+                night_mode.chat_id,
+                err
+            );
+        }
+    }
+
+    Ok(())
+}
+
+// periodically lock and unlock chats; state lives in the db so this picks up where it left off
+pub async fn run_scheduler(bot: crate::Bot, pool: &Pool<Postgres>) {
+    let mut interval = tokio::time::interval(SCHEDULER_INTERVAL);
+
+    loop {
+        interval.tick().await;
+        if let Err(err) = check_night_modes(&bot, pool).await {
+            log::error!("Failed to check night modes: {}", err);
+        }
+    }
+}
+
+pub async fn night_mode(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    // check for required conditions
+    tokio::try_join!(
+        perms::require_group_or_connection(bot, message), // needs a group or a connected PM
+        perms::require_user_admin(bot, message),          // user requires admin permissions
+        perms::require_bot_restrict_chat_members(bot, message) // bot requires RESTRICT_CHAT_MEMBERS permissions
+    )?;
+
+    let chat_id = utils::target_chat(message, pool).await?;
+
+    let args: Vec<_> = message
+        .text()
+        .map(|text| text.split_whitespace().skip(1).collect())
+        .unwrap_or_default();
+
+    match args[..] {
+        [] => match nightmode::get_night_mode(chat_id, pool).await? {
+            Some(night_mode) => {
+                bot.send_message(
+                    message.chat.id,
+                    format!(
+                        "Night mode locks this chat from {} to {} ({}).",
+                        night_mode.start_time.format("%H:%M"),
+                        night_mode.end_time.format("%H:%M"),
+                        html::escape(&night_mode.timezone)
+                    ),
+                )
+                .reply_to_message_id(message.id)
+                .await?;
+            }
+            None => {
+                bot.send_message(
+                    message.chat.id,
+                    "Night mode is off. Use <code>/nightmode &lt;start&gt; &lt;end&gt; &lt;timezone&gt;</code> to turn it on, eg: <code>/nightmode 01:00 06:00 Europe/Berlin</code>",
+                )
+                .reply_to_message_id(message.id)
+                .await?;
+            }
+        },
+        ["off"] => {
+            if let Some(night_mode) = nightmode::get_night_mode(chat_id, pool).await? {
+                // reopen the chat if it's currently locked
+                if night_mode.active {
+                    end_night(bot, &night_mode, pool).await?;
+                }
+                nightmode::delete_night_mode(chat_id, pool).await?;
+            }
+
+            bot.send_message(message.chat.id, "Night mode has been turned off.")
+                .reply_to_message_id(message.id)
+                .await?;
+        }
+        [start, end, timezone] => {
+            let (start_time, end_time) = match (
+                NaiveTime::parse_from_str(start, "%H:%M"),
+                NaiveTime::parse_from_str(end, "%H:%M"),
+            ) {
+                (Ok(start_time), Ok(end_time)) if start_time != end_time => (start_time, end_time),
+                _ => {
+                    bot.send_message(
+                        message.chat.id,
+                        "Start and end need to be two different times in 24-hour HH:MM format, eg: 01:00 06:00",
+                    )
+                    .reply_to_message_id(message.id)
+                    .await?;
+                    return Ok(());
+                }
+            };
+
+            let tz = match timezone.parse::<Tz>() {
+                Ok(tz) => tz,
+                Err(_) => {
+                    bot.send_message(
+                        message.chat.id,
+                        "That's not a timezone I know of; try something like <code>Europe/Berlin</code> or <code>UTC</code>.",
+                    )
+                    .reply_to_message_id(message.id)
+                    .await?;
+                    return Ok(());
+                }
+            };
+
+            nightmode::set_night_mode(
+                &NightMode {
+                    chat_id,
+                    start_time,
+                    end_time,
+                    timezone: tz.name().to_owned(),
+                    active: false,
+                    saved_permissions: None,
+                },
+                pool,
+            )
+            .await?;
+
+            bot.send_message(
+                message.chat.id,
+                format!(
+                    "Night mode will lock this chat from {} to {} ({}).",
+                    start_time.format("%H:%M"),
+                    end_time.format("%H:%M"),
+                    html::escape(tz.name())
+                ),
+            )
+            .reply_to_message_id(message.id)
+            .await?;
+        }
+        _ => {
+            bot.send_message(
+                message.chat.id,
+                "Usage: <code>/nightmode &lt;start&gt; &lt;end&gt; &lt;timezone&gt;</code> or <code>/nightmode off</code>",
+            )
+            .reply_to_message_id(message.id)
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms(hour, minute, 0)
+    }
+
+    #[test]
+    fn night_within_a_day() {
+        assert!(is_night(time(1, 0), time(6, 0), time(1, 0)));
+        assert!(is_night(time(1, 0), time(6, 0), time(5, 59)));
+        assert!(!is_night(time(1, 0), time(6, 0), time(6, 0)));
+        assert!(!is_night(time(1, 0), time(6, 0), time(0, 59)));
+    }
+
+    #[test]
+    fn night_wraps_around_midnight() {
+        assert!(is_night(time(23, 0), time(7, 0), time(23, 0)));
+        assert!(is_night(time(23, 0), time(7, 0), time(0, 0)));
+        assert!(is_night(time(23, 0), time(7, 0), time(6, 59)));
+        assert!(!is_night(time(23, 0), time(7, 0), time(7, 0)));
+        assert!(!is_night(time(23, 0), time(7, 0), time(12, 0)));
+    }
+
+    #[test]
+    fn night_ending_at_midnight() {
+        assert!(is_night(time(22, 0), time(0, 0), time(23, 59)));
+        assert!(!is_night(time(22, 0), time(0, 0), time(0, 0)));
+        assert!(!is_night(time(22, 0), time(0, 0), time(21, 59)));
+    }
+}
@@ -0,0 +1,197 @@
+use std::collections::HashSet;
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use sqlx::{Pool, Postgres};
+use teloxide::{
+    payloads::SendMessageSetters,
+    prelude2::*,
+    types::{ChatMemberStatus, InlineKeyboardButton, InlineKeyboardMarkup},
+    utils::html,
+};
+
+use crate::{
+    handlers::banning,
+    repo::activity,
+    utils::{self, admincache, perms, TimeSpan},
+};
+
+// number of inactive users listed before the rest are summarized
+const MAX_LISTED: usize = 50;
+
+// inactive users in a chat, without its admins
+async fn inactive_users(
+    bot: &crate::Bot,
+    chat_id: i64,
+    cutoff: DateTime<Utc>,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<Vec<(i64, Option<String>, DateTime<Utc>)>> {
+    let admins: HashSet<_> = admincache::get_admins(bot, chat_id)
+        .await?
+        .iter()
+        .map(|admin| admin.user.id)
+        .collect();
+
+    Ok(activity::get_inactive_users(chat_id, cutoff, pool)
+        .await?
+        .into_iter()
+        .filter(|(user_id, _, _)| !admins.contains(user_id))
+        .collect())
+}
+
+pub async fn inactive(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    // check for required conditions
+    tokio::try_join!(
+        perms::require_group(bot, message), // command needs to be in a public group
+        perms::require_restrict_chat_members(bot, message), // user requires RESTRICT_CHAT_MEMBERS permissions
+        perms::require_bot_restrict_chat_members(bot, message) // bot requires RESTRICT_CHAT_MEMBERS permissions
+    )?;
+
+    let span = match message
+        .text()
+        .and_then(|text| text.split_once(char::is_whitespace))
+        .map(|(_, args)| args.parse::<TimeSpan>())
+    {
+        Some(Ok(span)) => span,
+        Some(Err(err)) => {
+            bot.send_message(message.chat.id, err)
+                .reply_to_message_id(message.id)
+                .await?;
+            return Ok(());
+        }
+        None => {
+            bot.send_message(
+                message.chat.id,
+                "Usage: <code>/inactive &lt;duration&gt;</code>, eg: <code>/inactive 30d</code>",
+            )
+            .reply_to_message_id(message.id)
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let cutoff = message.date - chrono::Duration::seconds(span.seconds());
+    let users = inactive_users(bot, message.chat.id, cutoff, pool).await?;
+    if users.is_empty() {
+        bot.send_message(
+            message.chat.id,
+            format!("Everyone I've seen here has spoken in the last {}.", span),
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(());
+    }
+
+    let mut list: Vec<_> = users
+        .iter()
+        .take(MAX_LISTED)
+        .map(|(user_id, full_name, last_seen)| {
+            format!(
+                "- {} ({}), last seen {}",
+                html::user_mention(
+                    *user_id,
+                    &html::escape(full_name.as_deref().unwrap_or("Unknown user"))
+                ),
+                html::code_inline(&user_id.to_string()),
+                last_seen.format("%Y-%m-%d")
+            )
+        })
+        .collect();
+    if users.len() > MAX_LISTED {
+        list.push(format!("...and {} more", users.len() - MAX_LISTED));
+    }
+
+    let keyboard = InlineKeyboardMarkup::default().append_row(vec![
+        InlineKeyboardButton::callback(
+            format!("Kick all {}", users.len()),
+            format!("inactive:kick:{}", cutoff.timestamp()),
+        ),
+        InlineKeyboardButton::callback("Cancel".to_owned(), "inactive:cancel".to_owned()),
+    ]);
+
+    bot.send_message(
+        message.chat.id,
+        format!(
+            "{} user(s) haven't spoken in the last {}:\n{}",
+            users.len(),
+            span,
+            list.join("\n")
+        ),
+    )
+    .reply_markup(keyboard)
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn handle_callback(
+    bot: &crate::Bot,
+    query: &CallbackQuery,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let list_msg = match &query.message {
+        Some(message) => message,
+        None => return Ok(()),
+    };
+    let chat_id = list_msg.chat.id;
+
+    // only people who could have kicked them can confirm
+    if !perms::can_restrict_from_reply(bot, list_msg, query.from.id).await? {
+        bot.answer_callback_query(query.id.clone())
+            .text("You're missing the required permission for this action: CAN_RESTRICT_MEMBERS.")
+            .show_alert(true)
+            .await?;
+        return Ok(());
+    }
+
+    let data = query.data.as_deref().unwrap_or_default();
+    let parts: Vec<_> = data.split(':').collect();
+    if parts.len() != 3 {
+        bot.answer_callback_query(query.id.clone()).await?;
+        bot.edit_message_text(chat_id, list_msg.id, "Cancelled.")
+            .await?;
+        return Ok(());
+    }
+
+    let cutoff = DateTime::<Utc>::from_utc(
+        NaiveDateTime::from_timestamp(parts[2].parse::<i64>()?, 0),
+        Utc,
+    );
+
+    bot.answer_callback_query(query.id.clone()).await?;
+    bot.edit_message_text(chat_id, list_msg.id, "Kicking inactive users...")
+        .await?;
+
+    // check everyone again, things may have changed since the list was made
+    let targets = inactive_users(bot, chat_id, cutoff, pool)
+        .await?
+        .into_iter()
+        .map(|(user_id, _, _)| Ok(user_id))
+        .collect();
+    let (summary, _) = utils::mass_action(targets, "Kicked", |user_id| async move {
+        let chat_member = banning::check_mass_target(bot, chat_id, user_id, pool).await?;
+        if matches!(
+            chat_member.status(),
+            ChatMemberStatus::Banned | ChatMemberStatus::Left
+        ) {
+            // nothing left to prune
+            activity::forget_user(chat_id, user_id, pool).await.ok();
+            return Err("This user isn't in the chat!".to_owned());
+        }
+
+        utils::retry_rate_limited(|| banning::kick_user(bot, chat_id, user_id))
+            .await
+            .map_err(|err| html::escape(&err.to_string()))?;
+        activity::forget_user(chat_id, user_id, pool).await.ok();
+        Ok(())
+    })
+    .await;
+
+    bot.edit_message_text(chat_id, list_msg.id, summary).await?;
+
+    Ok(())
+}
@@ -0,0 +1,273 @@
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+use futures::{stream, StreamExt};
+use lazy_static::lazy_static;
+use teloxide::{payloads::SendMessageSetters, prelude2::*, ApiError, RequestError};
+
+use crate::utils::perms;
+
+// number of delete requests allowed in flight at once
+const MAX_CONCURRENT_DELETES: usize = 10;
+
+// most messages a single purge goes through, each one is a separate API call
+const MAX_PURGE_MESSAGES: i32 = 1000;
+
+lazy_static! {
+    // message IDs marked with /purgefrom, keyed by (chat ID, user ID)
+    static ref PURGE_MARKERS: Mutex<HashMap<(i64, i64), i32>> = Mutex::new(HashMap::new());
+}
+
+enum DeleteResult {
+    Deleted,
+    NotFound,
+    Failed,
+}
+
+async fn delete_one(bot: &crate::Bot, chat_id: i64, message_id: i32) -> DeleteResult {
+    loop {
+        match bot.delete_message(chat_id, message_id).await {
+            Ok(_) => return DeleteResult::Deleted,
+            // message IDs aren't contiguous, some of them might not exist
+            Err(RequestError::Api(ApiError::MessageToDeleteNotFound)) => {
+                return DeleteResult::NotFound
+            }
+            // back off and try again if we're being rate limited
+            Err(RequestError::RetryAfter(secs)) => {
+                tokio::time::sleep(Duration::from_secs(secs as u64)).await;
+            }
+            Err(_) => return DeleteResult::Failed,
+        }
+    }
+}
+
+// delete every message between start and end (inclusive), returning (deleted, failed)
+async fn delete_range(bot: &crate::Bot, chat_id: i64, start: i32, end: i32) -> (usize, usize) {
+    let results: Vec<DeleteResult> = stream::iter(start..=end)
+        .map(|id| delete_one(bot, chat_id, id))
+        .buffer_unordered(MAX_CONCURRENT_DELETES)
+        .collect()
+        .await;
+
+    results
+        .iter()
+        .fold((0, 0), |(deleted, failed), res| match res {
+            DeleteResult::Deleted => (deleted + 1, failed),
+            DeleteResult::NotFound => (deleted, failed),
+            // old messages, service messages and missing rights all end up here
+            DeleteResult::Failed => (deleted, failed + 1),
+        })
+}
+
+// purge a range of messages before the command, then the command itself
+async fn purge_range(
+    bot: &crate::Bot,
+    message: &Message,
+    start: i32,
+    end: i32,
+    silent: bool,
+) -> anyhow::Result<()> {
+    // replying to an ancient message would mean hundreds of thousands of requests
+    if end - start >= MAX_PURGE_MESSAGES {
+        bot.send_message(
+            message.chat.id,
+            format!(
+                "That's too many messages, I can purge up to {} at a time.",
+                MAX_PURGE_MESSAGES
+            ),
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(());
+    }
+
+    let (deleted, failed) = delete_range(bot, message.chat.id, start, end).await;
+    bot.delete_message(message.chat.id, message.id).await.ok();
+
+    // spurge doesn't leave anything behind
+    if silent {
+        return Ok(());
+    }
+
+    let mut text = format!("Purged {} message(s).", deleted);
+    if failed > 0 {
+        text.push_str(&format!("\n{} message(s) could not be deleted.", failed));
+    }
+
+    bot.send_message(message.chat.id, text).await?;
+
+    Ok(())
+}
+
+pub async fn purge(bot: &crate::Bot, message: &Message, silent: bool) -> anyhow::Result<()> {
+    // check for required conditions
+    tokio::try_join!(
+        perms::require_group(bot, message), // command needs to be in a public group
+        perms::require_can_delete_messages(bot, message), // user requires CAN_DELETE_MESSAGES permissions
+        perms::require_bot_can_delete_messages(bot, message), // bot requires CAN_DELETE_MESSAGES permissions
+    )?;
+
+    // optional number of messages to purge
+    let count = match message
+        .text()
+        .and_then(|text| text.split_whitespace().nth(1))
+    {
+        Some(arg) => match arg.parse::<i32>() {
+            Ok(n) if n > 0 => Some(n),
+            _ => {
+                bot.send_message(message.chat.id, "That's not a valid number of messages!")
+                    .reply_to_message_id(message.id)
+                    .await?;
+                return Ok(());
+            }
+        },
+        None => None,
+    };
+
+    match (message.reply_to_message(), count) {
+        // purge n messages starting from the replied-to message
+        (Some(prev_msg), Some(n)) => {
+            let end = (prev_msg.id + n - 1).min(message.id - 1);
+            purge_range(bot, message, prev_msg.id, end, silent).await?;
+        }
+        // purge everything from the replied-to message up to the command
+        (Some(prev_msg), None) => {
+            purge_range(bot, message, prev_msg.id, message.id - 1, silent).await?;
+        }
+        // purge the last n messages before the command
+        (None, Some(n)) => {
+            purge_range(
+                bot,
+                message,
+                (message.id - n).max(1),
+                message.id - 1,
+                silent,
+            )
+            .await?;
+        }
+        (None, None) => {
+            bot.send_message(
+                message.chat.id,
+                "Reply to a message to select where to start purging from.",
+            )
+            .reply_to_message_id(message.id)
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn delete(bot: &crate::Bot, message: &Message) -> anyhow::Result<()> {
+    // check for required conditions
+    tokio::try_join!(
+        perms::require_group(bot, message), // command needs to be in a public group
+        perms::require_can_delete_messages(bot, message), // user requires CAN_DELETE_MESSAGES permissions
+        perms::require_bot_can_delete_messages(bot, message), // bot requires CAN_DELETE_MESSAGES permissions
+    )?;
+
+    if let Some(prev_msg) = message.reply_to_message() {
+        match delete_one(bot, message.chat.id, prev_msg.id).await {
+            DeleteResult::Deleted | DeleteResult::NotFound => {
+                bot.delete_message(message.chat.id, message.id).await.ok();
+            }
+            DeleteResult::Failed => {
+                bot.send_message(message.chat.id, "Couldn't delete that message!")
+                    .reply_to_message_id(message.id)
+                    .await?;
+            }
+        }
+    } else {
+        bot.send_message(message.chat.id, "Reply to a message to delete it!")
+            .reply_to_message_id(message.id)
+            .await?;
+    }
+
+    Ok(())
+}
+
+pub async fn purge_from(bot: &crate::Bot, message: &Message) -> anyhow::Result<()> {
+    // check for required conditions
+    tokio::try_join!(
+        perms::require_group(bot, message), // command needs to be in a public group
+        perms::require_can_delete_messages(bot, message), // user requires CAN_DELETE_MESSAGES permissions
+        perms::require_bot_can_delete_messages(bot, message), // bot requires CAN_DELETE_MESSAGES permissions
+    )?;
+
+    let user = match message.from() {
+        Some(user) => user,
+        None => return Ok(()),
+    };
+
+    if let Some(prev_msg) = message.reply_to_message() {
+        PURGE_MARKERS
+            .lock()
+            .unwrap()
+            .insert((message.chat.id, user.id), prev_msg.id);
+
+        bot.send_message(
+            message.chat.id,
+            "Message marked for deletion. Reply to another message with /purgeto to delete everything in between.",
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+    } else {
+        bot.send_message(
+            message.chat.id,
+            "Reply to a message to mark where to purge from.",
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+    }
+
+    Ok(())
+}
+
+pub async fn purge_to(bot: &crate::Bot, message: &Message) -> anyhow::Result<()> {
+    // check for required conditions
+    tokio::try_join!(
+        perms::require_group(bot, message), // command needs to be in a public group
+        perms::require_can_delete_messages(bot, message), // user requires CAN_DELETE_MESSAGES permissions
+        perms::require_bot_can_delete_messages(bot, message), // bot requires CAN_DELETE_MESSAGES permissions
+    )?;
+
+    let user = match message.from() {
+        Some(user) => user,
+        None => return Ok(()),
+    };
+
+    let prev_msg = match message.reply_to_message() {
+        Some(prev_msg) => prev_msg,
+        None => {
+            bot.send_message(
+                message.chat.id,
+                "Reply to a message to mark where to purge to.",
+            )
+            .reply_to_message_id(message.id)
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let start = PURGE_MARKERS
+        .lock()
+        .unwrap()
+        .remove(&(message.chat.id, user.id));
+
+    match start {
+        Some(start) => {
+            // users might mark the range backwards
+            let (start, end) = (start.min(prev_msg.id), start.max(prev_msg.id));
+            purge_range(bot, message, start, end, false).await?;
+        }
+        None => {
+            bot.send_message(
+                message.chat.id,
+                "Mark a message with /purgefrom first, then reply to another with /purgeto.",
+            )
+            .reply_to_message_id(message.id)
+            .await?;
+        }
+    }
+
+    Ok(())
+}
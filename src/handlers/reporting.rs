@@ -0,0 +1,352 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use futures::future::join_all;
+use lazy_static::lazy_static;
+use sqlx::{Pool, Postgres};
+use teloxide::{
+    payloads::SendMessageSetters,
+    prelude2::*,
+    types::{ChatKind, InlineKeyboardButton, InlineKeyboardMarkup, PublicChatKind},
+    utils::html,
+};
+
+use crate::{
+    handlers::banning,
+    repo::reports,
+    utils::{self, admincache, perms},
+    BOT_ID,
+};
+
+// how long a user has to wait between reports in the same chat
+const REPORT_COOLDOWN: Duration = Duration::from_secs(60);
+
+lazy_static! {
+    // when each (chat, user) last sent a report
+    static ref LAST_REPORTS: Mutex<HashMap<(i64, i64), Instant>> = Mutex::new(HashMap::new());
+}
+
+// record a report, returning false if the user is still on cooldown
+fn check_cooldown(chat_id: i64, user_id: i64) -> bool {
+    let mut last_reports = LAST_REPORTS.lock().unwrap();
+    last_reports.retain(|_, reported_at| reported_at.elapsed() < REPORT_COOLDOWN);
+
+    if last_reports.contains_key(&(chat_id, user_id)) {
+        return false;
+    }
+    last_reports.insert((chat_id, user_id), Instant::now());
+    true
+}
+
+// build a link that opens the reported message
+fn message_link(message: &Message, message_id: i32) -> Option<String> {
+    if let ChatKind::Public(chat) = &message.chat.kind {
+        if let PublicChatKind::Supergroup(sg) = &chat.kind {
+            return Some(match &sg.username {
+                Some(username) => format!("https://t.me/{}/{}", username, message_id),
+                None => format!(
+                    "https://t.me/c/{}/{}",
+                    // private supergroup IDs are prefixed with -100
+                    -message.chat.id - 1_000_000_000_000,
+                    message_id
+                ),
+            });
+        }
+    }
+
+    None
+}
+
+fn report_keyboard(chat_id: i64, user_id: i64, message_id: i32) -> InlineKeyboardMarkup {
+    let button = |text: &str, action: &str| {
+        InlineKeyboardButton::callback(
+            text.to_owned(),
+            format!("report:{}:{}:{}:{}", action, chat_id, user_id, message_id),
+        )
+    };
+
+    // channels can only be banned
+    let actions = if utils::is_channel(user_id) {
+        vec![button("Ban", "ban")]
+    } else {
+        vec![button("Kick", "kick"), button("Ban", "ban")]
+    };
+
+    InlineKeyboardMarkup::default()
+        .append_row(actions)
+        .append_row(vec![
+            button("Delete message", "del"),
+            button("Mark resolved", "resolve"),
+        ])
+}
+
+pub async fn report(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    // check for required conditions
+    tokio::try_join!(
+        perms::require_group(bot, message), // command needs to be in a public group
+    )?;
+
+    // admins may have turned reporting off for this chat
+    if !reports::get_reports_enabled(message.chat.id, pool).await? {
+        return Ok(());
+    }
+
+    // reports need to target a message
+    let prev_msg = match message.reply_to_message() {
+        Some(prev_msg) => prev_msg,
+        None => {
+            bot.send_message(message.chat.id, "Reply to the message you want to report!")
+                .reply_to_message_id(message.id)
+                .await?;
+            return Ok(());
+        }
+    };
+
+    // don't report admins, anonymous ones don't have a sender ID
+    let reported_id = match utils::sender_id(prev_msg) {
+        Some(id) if perms::is_user_admin(bot, message, id).await.is_err() => id,
+        _ => {
+            bot.send_message(message.chat.id, "Why would I report an admin?")
+                .reply_to_message_id(message.id)
+                .await?;
+            return Ok(());
+        }
+    };
+
+    // spamming reports would spam every admin's PMs too
+    if let Some(reporter_id) = utils::sender_id(message) {
+        if !check_cooldown(message.chat.id, reporter_id) {
+            bot.send_message(
+                message.chat.id,
+                "You've reported something recently, give the admins a minute!",
+            )
+            .reply_to_message_id(message.id)
+            .await?;
+            return Ok(());
+        }
+    }
+
+    // everything after the command or mention is the reason
+    let reason = message
+        .text()
+        .and_then(|text| text.split_once(char::is_whitespace))
+        .map(|(_, reason)| reason.trim().to_owned())
+        .filter(|reason| !reason.is_empty());
+
+    let chat_title = message.chat.title().unwrap_or("the chat");
+    let mut text = format!(
+        "{} reported {} in {}.",
+        utils::sender_mention(message),
+        utils::sender_mention(prev_msg),
+        html::bold(&html::escape(chat_title)),
+    );
+    if let Some(reason) = &reason {
+        text.push_str(&format!("\nReason: {}", html::escape(reason)));
+    }
+    if let Some(link) = message_link(message, prev_msg.id) {
+        text.push_str(&format!("\n{}", html::link(&link, "Go to message")));
+    }
+
+    let admins = admincache::get_admins(bot, message.chat.id).await?;
+    let admins: Vec<_> = admins.iter().filter(|admin| !admin.user.is_bot).collect();
+
+    // notify every admin in their PMs, this only works if they've started the bot
+    let results = join_all(admins.iter().map(|admin| {
+        bot.send_message(admin.user.id, text.clone())
+            .reply_markup(report_keyboard(message.chat.id, reported_id, prev_msg.id))
+            .send()
+    }))
+    .await;
+
+    for (admin, res) in admins.iter().zip(results.iter()) {
+        if let Ok(copy) = res {
+            reports::add_report_copy(message.chat.id, prev_msg.id, admin.user.id, copy.id, pool)
+                .await?;
+        }
+    }
+
+    // mention the admins we couldn't reach instead
+    let mentions: Vec<_> = admins
+        .iter()
+        .zip(results.iter())
+        .filter(|(admin, res)| res.is_err() && !admin.kind.is_anonymous())
+        .map(|(admin, _)| html::user_mention(admin.user.id, &html::escape(&admin.user.first_name)))
+        .collect();
+
+    let mut reply = format!(
+        "Reported {} to the admins.",
+        utils::sender_mention(prev_msg)
+    );
+    if !mentions.is_empty() {
+        reply.push_str(&format!("\n{}", mentions.join(", ")));
+    }
+
+    bot.send_message(message.chat.id, reply)
+        .reply_to_message_id(prev_msg.id)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn toggle_reports(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    // check for required conditions
+    tokio::try_join!(
+        perms::require_group_or_connection(bot, message), // needs a group or a connected PM
+        perms::require_user_admin(bot, message),          // user requires admin permissions
+    )?;
+
+    let chat_id = utils::target_chat(message, pool).await?;
+
+    match message
+        .text()
+        .and_then(|text| text.split_whitespace().nth(1))
+    {
+        Some("on") | Some("yes") => {
+            reports::set_reports_enabled(chat_id, true, pool).await?;
+            bot.send_message(message.chat.id, "Reporting has been turned on.")
+                .reply_to_message_id(message.id)
+                .await?;
+        }
+        Some("off") | Some("no") => {
+            reports::set_reports_enabled(chat_id, false, pool).await?;
+            bot.send_message(message.chat.id, "Reporting has been turned off.")
+                .reply_to_message_id(message.id)
+                .await?;
+        }
+        _ => {
+            let enabled = reports::get_reports_enabled(chat_id, pool).await?;
+            bot.send_message(
+                message.chat.id,
+                format!(
+                    "Reporting is currently {} in this chat. Use <code>/reports on</code> or <code>/reports off</code> to change it.",
+                    if enabled { "on" } else { "off" }
+                ),
+            )
+            .reply_to_message_id(message.id)
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn handle_callback(
+    bot: &crate::Bot,
+    query: &CallbackQuery,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let data = query.data.as_deref().unwrap_or_default();
+    let parts: Vec<_> = data.split(':').collect();
+    if parts.len() != 5 {
+        return Ok(());
+    }
+
+    let action = parts[1];
+    let chat_id = parts[2].parse::<i64>()?;
+    let user_id = parts[3].parse::<i64>()?;
+    let message_id = parts[4].parse::<i32>()?;
+
+    // make sure whoever pressed the button is allowed to do this
+    let allowed = match action {
+        "kick" | "ban" => perms::can_user_restrict_members(bot, chat_id, query.from.id).await?,
+        "del" => perms::can_user_delete_messages(bot, chat_id, query.from.id).await?,
+        _ => perms::is_chat_admin(bot, chat_id, query.from.id).await?,
+    };
+    if !allowed {
+        bot.answer_callback_query(query.id.clone())
+            .text("You're missing the required permissions for this action.")
+            .show_alert(true)
+            .await?;
+        return Ok(());
+    }
+
+    // the report might be old, so check the user the same way /ban and /kick would
+    if matches!(action, "kick" | "ban") {
+        let checked = if !perms::can_user_restrict_members(bot, chat_id, *BOT_ID).await? {
+            Err(
+                "I'm missing the required permission for this action: CAN_RESTRICT_MEMBERS."
+                    .to_owned(),
+            )
+        } else {
+            banning::check_mass_target(bot, chat_id, user_id, pool)
+                .await
+                .map(|_| ())
+        };
+        if let Err(reason) = checked {
+            bot.answer_callback_query(query.id.clone())
+                .text(reason)
+                .show_alert(true)
+                .await?;
+            return Ok(());
+        }
+    }
+
+    let outcome = match action {
+        "kick" => banning::kick_user(bot, chat_id, user_id)
+            .await
+            .map(|_| "Kicked!"),
+        "ban" => banning::ban_user(bot, chat_id, user_id, None)
+            .await
+            .map(|_| "Banned!"),
+        "del" => bot
+            .delete_message(chat_id, message_id)
+            .await
+            .map(|_| "Deleted!")
+            .map_err(Into::into),
+        _ => Ok("Resolved!"),
+    };
+
+    let outcome = match outcome {
+        Ok(text) => text.to_owned(),
+        Err(err) => format!("Failed: {}", err),
+    };
+
+    bot.answer_callback_query(query.id.clone())
+        .text(outcome.clone())
+        .await?;
+
+    // every admin got their own copy of the report, reports sent before copies were tracked only have this one
+    let mut copies = reports::get_report_copies(chat_id, message_id, pool).await?;
+    if let Some(report_msg) = &query.message {
+        if !copies.contains(&(report_msg.chat.id, report_msg.id)) {
+            copies.push((report_msg.chat.id, report_msg.id));
+        }
+    }
+
+    // record what happened as a reply, editing the report would lose its formatting
+    let note = format!(
+        "{} ({})",
+        html::escape(&outcome),
+        html::user_mention(query.from.id, &html::escape(&query.from.first_name)),
+    );
+    join_all(copies.iter().map(|(admin_id, copy_id)| {
+        bot.send_message(*admin_id, note.clone())
+            .reply_to_message_id(*copy_id)
+            .send()
+    }))
+    .await;
+
+    // resolved reports don't need any more actions
+    if action == "resolve" {
+        join_all(
+            copies.iter().map(|(admin_id, copy_id)| {
+                bot.edit_message_reply_markup(*admin_id, *copy_id).send()
+            }),
+        )
+        .await;
+        reports::remove_report_copies(chat_id, message_id, pool).await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,293 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use sqlx::{Pool, Postgres};
+use teloxide::{payloads::SendMessageSetters, prelude2::*, utils::html};
+
+use crate::{
+    entities::ScriptFilter,
+    handlers::banning,
+    repo::scriptfilters,
+    utils::{self, perms, FilterAction},
+};
+
+// share of letters a script needs by default before a message is filtered
+const DEFAULT_THRESHOLD: i32 = 50;
+
+// a script filter ready to be matched against
+struct CompiledFilter {
+    script: String,
+    regex: Regex,
+    threshold: i32,
+    action: Option<FilterAction>,
+}
+
+lazy_static! {
+    // compiled script filters per chat, dropped whenever a chat's filters change
+    static ref COMPILED_FILTERS: Mutex<HashMap<i64, Arc<Vec<CompiledFilter>>>> =
+        Mutex::new(HashMap::new());
+}
+
+// match a single character of a unicode script, eg: 'cyrillic' or 'han'
+fn script_regex(script: &str) -> Option<Regex> {
+    // only allow plain script names into the pattern
+    if script.is_empty() || !script.chars().all(|c| c.is_ascii_alphabetic() || c == '_') {
+        return None;
+    }
+
+    Regex::new(&format!(r"\p{{Script={}}}", script)).ok()
+}
+
+// get a chat's script filters, compiling them only the first time they're needed
+async fn compiled_filters(
+    chat_id: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<Arc<Vec<CompiledFilter>>> {
+    if let Some(filters) = COMPILED_FILTERS.lock().unwrap().get(&chat_id) {
+        return Ok(filters.clone());
+    }
+
+    let filters: Vec<_> = scriptfilters::get_script_filters(chat_id, pool)
+        .await?
+        .into_iter()
+        .filter_map(|filter| {
+            Some(CompiledFilter {
+                regex: script_regex(&filter.script)?,
+                threshold: filter.threshold,
+                action: filter
+                    .action
+                    .as_deref()
+                    .and_then(|action| action.parse::<FilterAction>().ok()),
+                script: filter.script,
+            })
+        })
+        .collect();
+
+    let filters = Arc::new(filters);
+    COMPILED_FILTERS
+        .lock()
+        .unwrap()
+        .insert(chat_id, filters.clone());
+    Ok(filters)
+}
+
+fn invalidate_filters(chat_id: i64) {
+    COMPILED_FILTERS.lock().unwrap().remove(&chat_id);
+}
+
+// percentage of the letters in some text that belong to a script
+fn script_share(text: &str, regex: &Regex) -> i32 {
+    let letters = text.chars().filter(|c| c.is_alphabetic()).count();
+    if letters == 0 {
+        return 0;
+    }
+
+    let matched = regex
+        .find_iter(text)
+        .filter(|m| m.as_str().chars().all(char::is_alphabetic))
+        .count();
+
+    (matched * 100 / letters) as i32
+}
+
+// delete messages mostly written in a filtered script, returning true if the message was removed
+pub async fn handle_message(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<bool> {
+    if message.chat.is_private() {
+        return Ok(false);
+    }
+
+    let text = match message.text().or_else(|| message.caption()) {
+        Some(text) => text,
+        None => return Ok(false),
+    };
+
+    let filters = compiled_filters(message.chat.id, pool).await?;
+    let filter = filters
+        .iter()
+        .find(|filter| script_share(text, &filter.regex) >= filter.threshold);
+    let filter = match filter {
+        Some(filter) => filter,
+        None => return Ok(false),
+    };
+
+    // admins and approved users can write however they like
+    let sender_id = match utils::sender_id(message) {
+        Some(sender_id) => sender_id,
+        None => return Ok(false),
+    };
+    if perms::is_user_exempt(bot, message.chat.id, sender_id, pool).await? {
+        return Ok(false);
+    }
+
+    log::info!(
+        "Removing {} message from {} in chat {}",
+        filter.script,
+        sender_id,
+        message.chat.id
+    );
+    bot.delete_message(message.chat.id, message.id).await.ok();
+
+    if let Some(action) = filter.action {
+        if banning::punish_user(bot, message.chat.id, sender_id, action).await? {
+            bot.send_message(
+                message.chat.id,
+                format!(
+                    "{} was {} for writing in {}.",
+                    utils::sender_mention(message),
+                    action.past_tense(),
+                    html::escape(&filter.script)
+                ),
+            )
+            .await?;
+        }
+    }
+
+    Ok(true)
+}
+
+pub async fn script_filter(
+    bot: &crate::Bot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    // check for required conditions
+    tokio::try_join!(
+        perms::require_group_or_connection(bot, message), // needs a group or a connected PM
+        perms::require_restrict_chat_members(bot, message), // user requires RESTRICT_CHAT_MEMBERS permissions
+        perms::require_bot_restrict_chat_members(bot, message), // bot requires RESTRICT_CHAT_MEMBERS permissions
+        perms::require_bot_can_delete_messages(bot, message), // bot requires CAN_DELETE_MESSAGES permissions
+    )?;
+
+    let chat_id = utils::target_chat(message, pool).await?;
+
+    let text = message.text().unwrap_or_default().to_lowercase();
+    let args: Vec<_> = text.split_whitespace().skip(1).collect();
+
+    match args[..] {
+        [] | ["list"] => {
+            let filters = scriptfilters::get_script_filters(chat_id, pool).await?;
+            let text = if filters.is_empty() {
+                "No script filters are set up in this chat.".to_owned()
+            } else {
+                let list: Vec<_> = filters
+                    .iter()
+                    .map(|filter| {
+                        format!(
+                            "- {}: {}% or more, {}",
+                            html::escape(&filter.script),
+                            filter.threshold,
+                            html::escape(filter.action.as_deref().unwrap_or("delete"))
+                        )
+                    })
+                    .collect();
+                format!("Script filters in this chat:\n{}", list.join("\n"))
+            };
+
+            bot.send_message(message.chat.id, text)
+                .reply_to_message_id(message.id)
+                .await?;
+        }
+        ["add", script, ref rest @ ..] if rest.len() <= 2 => {
+            if script_regex(script).is_none() {
+                bot.send_message(
+                    message.chat.id,
+                    "That's not a script I know of; try something like cyrillic, arabic, han, hebrew or greek.",
+                )
+                .reply_to_message_id(message.id)
+                .await?;
+                return Ok(());
+            }
+
+            // threshold and action are both optional
+            let mut threshold = DEFAULT_THRESHOLD;
+            let mut action = None;
+            for arg in rest {
+                if let Some(Ok(percent)) = arg.strip_suffix('%').map(|p| p.parse::<i32>()) {
+                    threshold = percent;
+                } else if let Ok(percent) = arg.parse::<i32>() {
+                    threshold = percent;
+                } else if *arg == "delete" {
+                    action = None;
+                } else {
+                    match arg.parse::<FilterAction>() {
+                        Ok(filter_action) => action = Some(filter_action),
+                        Err(_) => {
+                            bot.send_message(
+                                message.chat.id,
+                                "Allowed actions: delete, ban, kick, mute",
+                            )
+                            .reply_to_message_id(message.id)
+                            .await?;
+                            return Ok(());
+                        }
+                    }
+                }
+            }
+            if !(1..=100).contains(&threshold) {
+                bot.send_message(
+                    message.chat.id,
+                    "The threshold needs to be a percentage between 1 and 100.",
+                )
+                .reply_to_message_id(message.id)
+                .await?;
+                return Ok(());
+            }
+
+            scriptfilters::add_script_filter(
+                &ScriptFilter {
+                    chat_id,
+                    script: script.to_owned(),
+                    threshold,
+                    action: action.map(|action| action.to_string()),
+                },
+                pool,
+            )
+            .await?;
+            invalidate_filters(chat_id);
+
+            let mut text = format!(
+                "Messages that are {}% or more {} will be deleted",
+                threshold,
+                html::escape(script)
+            );
+            if let Some(action) = action {
+                text.push_str(&format!(" and their senders {}", action.past_tense()));
+            }
+            text.push('.');
+
+            bot.send_message(message.chat.id, text)
+                .reply_to_message_id(message.id)
+                .await?;
+        }
+        ["remove" | "rm" | "del", script] => {
+            let text = if scriptfilters::remove_script_filter(chat_id, script, pool).await? {
+                invalidate_filters(chat_id);
+                format!("Removed the {} script filter.", html::escape(script))
+            } else {
+                "There's no filter for that script.".to_owned()
+            };
+
+            bot.send_message(message.chat.id, text)
+                .reply_to_message_id(message.id)
+                .await?;
+        }
+        _ => {
+            bot.send_message(
+                message.chat.id,
+                "Usage: <code>/scriptfilter add &lt;script&gt; [threshold%] [delete|ban|kick|mute]</code>, <code>/scriptfilter remove &lt;script&gt;</code> or <code>/scriptfilter list</code>",
+            )
+            .reply_to_message_id(message.id)
+            .await?;
+        }
+    }
+
+    Ok(())
+}
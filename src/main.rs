@@ -1,15 +1,25 @@
 use dotenv::dotenv;
-use handlers::{admin, banning, filters, misc, muting, save_chat_handler, save_user_handler};
+use handlers::{
+    admin, admininfo, anonymous, antichannel, antidupe, antiraid, appeals, approvals, banning,
+    connection, disabling, filters, linkfilter, linking, mediafilter, misc, moderators, muting,
+    namefilter, nightmode, pruning, purging, reporting, save_activity_handler, save_chat_handler,
+    save_user_handler, scriptfilter,
+};
 use lazy_static::lazy_static;
 use sqlx::{postgres::PgPoolOptions, Pool, Postgres};
+use std::time::Duration;
+
 use teloxide::{
     adaptors::DefaultParseMode,
+    dispatching::update_listeners::{self, AsUpdateStream, UpdateListener},
     dispatching2::UpdateFilterExt,
     prelude2::*,
-    types::{ChatAction, ParseMode},
+    types::{
+        AllowedUpdate, ChatAction, ChatMember, ChatMemberStatus, ChatMemberUpdated, ParseMode,
+    },
     utils::command::BotCommand,
 };
-use utils::PinMode;
+use utils::{admincache, PinMode};
 
 pub mod entities;
 pub mod handlers;
@@ -18,6 +28,14 @@ pub mod utils;
 
 type Bot = AutoSend<DefaultParseMode<teloxide::Bot>>;
 
+// chat member updates have to be asked for explicitly
+const ALLOWED_UPDATES: [AllowedUpdate; 4] = [
+    AllowedUpdate::Message,
+    AllowedUpdate::CallbackQuery,
+    AllowedUpdate::ChatMember,
+    AllowedUpdate::MyChatMember,
+];
+
 #[derive(BotCommand, Clone)]
 #[command(rename = "lowercase", description = "List of supported commands:")]
 enum Command {
@@ -25,36 +43,142 @@ enum Command {
     Help,
     #[command(description = "Get a user's ID.")]
     Id,
-    #[command(description = "Ban a user.")]
+    #[command(description = "Ban one or more users.")]
     Ban,
-    #[command(description = "Temporarily ban a user.")]
+    #[command(
+        description = "Temporarily ban a user, eg: /tban @user 1d12h or /tban @user until friday 9am"
+    )]
     Tban,
-    #[command(description = "Kick a user")]
+    #[command(description = "Kick one or more users")]
     Kick,
     #[command(description = "Kick yourself")]
     Kickme,
-    #[command(description = "Unban a user")]
+    #[command(description = "Unban one or more users")]
     Unban,
-    #[command(description = "Mute a user.")]
+    #[command(description = "Mute one or more users.")]
     Mute,
-    #[command(description = "Temporarily mute a user.")]
+    #[command(
+        description = "Temporarily mute a user, eg: /tmute @user 2h or /tmute @user until 18:00"
+    )]
     Tmute,
     #[command(description = "Unmute a user.")]
     Unmute,
-    #[command(description = "Promote a user")]
+    #[command(description = "Promote a user, eg: /promote @user mod Helper")]
     Promote,
     #[command(description = "Demote a user")]
     Demote,
+    #[command(description = "Set an admin's custom title, eg: /title @user Helper")]
+    Title,
+    #[command(description = "Manage the rights presets used by /promote")]
+    Presets,
     #[command(description = "Pin a message")]
     Pin(PinMode),
     #[command(description = "Get the chat's invite link")]
     Invitelink,
+    #[command(description = "Refresh the cached list of admins")]
+    Admincache,
+    #[command(description = "List the chat's owner and admins")]
+    Adminlist,
+    #[command(description = "Show a user's rights and restrictions, eg: /perms @user")]
+    Perms,
+    #[command(description = "Show which rights I'm missing for each module")]
+    Botperms,
     #[command(description = "Save a note in this chat")]
     Save,
     #[command(
         description = "Get a saved note in this chat. Can be used as <code>#notename</code> or <code>/get notename</code>"
     )]
     Get,
+    #[command(
+        description = "Delete all messages from the replied-to message, or the last n messages"
+    )]
+    Purge,
+    #[command(description = "Silently purge messages")]
+    Spurge,
+    #[command(description = "Delete the replied-to message")]
+    Del,
+    #[command(description = "Mark a message to purge from")]
+    Purgefrom,
+    #[command(description = "Purge up to the replied-to message")]
+    Purgeto,
+    #[command(description = "Report a message to the chat's admins")]
+    Report,
+    #[command(description = "Turn reporting on or off in this chat")]
+    Reports,
+    #[command(description = "Approve a user, exempting them from automated moderation")]
+    Approve,
+    #[command(description = "Unapprove a user")]
+    Unapprove,
+    #[command(description = "List approved users in this chat")]
+    Approved,
+    #[command(description = "Unapprove all users in this chat")]
+    Unapproveall,
+    #[command(description = "Lock the chat every night, eg: /nightmode 01:00 06:00 Europe/Berlin")]
+    Nightmode,
+    #[command(description = "Ban everyone who joins for a while, eg: /antiraid 6h")]
+    Antiraid,
+    #[command(description = "Restrict some of a user's permissions, eg: /restrict @user media 1h")]
+    Restrict,
+    #[command(description = "Lift a user's restrictions")]
+    Unrestrict,
+    #[command(description = "Appeal a ban in PM, eg: /appeal @chat I'm sorry")]
+    Appeal,
+    #[command(
+        description = "Set the chat's timezone for end times, eg: /settimezone Europe/Berlin"
+    )]
+    Settimezone,
+    #[command(description = "List users who haven't spoken in a while, eg: /inactive 30d")]
+    Inactive,
+    #[command(
+        description = "Act on users whose name matches a pattern, eg: /namefilter add crypto ban"
+    )]
+    Namefilter,
+    #[command(description = "Delete messages sent as a channel and ban the channel")]
+    Antichannel,
+    #[command(description = "Filter links by domain, eg: /linkmode blocklist mute")]
+    Linkmode,
+    #[command(description = "Add domains to the link allowlist, eg: /allowdomain example.com")]
+    Allowdomain,
+    #[command(description = "Add domains to the link blocklist, eg: /blockdomain example.com")]
+    Blockdomain,
+    #[command(
+        description = "Filter messages written in a script, eg: /scriptfilter add cyrillic 60% mute"
+    )]
+    Scriptfilter,
+    #[command(description = "Reply to media to delete it on sight, eg: /blockmedia mute")]
+    Blockmedia,
+    #[command(description = "Reply to blocked media to allow it again")]
+    Unblockmedia,
+    #[command(description = "Mute users posting the same message, eg: /antidupe users 3")]
+    Antidupe,
+    #[command(description = "Give a user a bot role, eg: /addmod @user helper")]
+    Addmod,
+    #[command(description = "Take away a user's bot role")]
+    Rmmod,
+    #[command(description = "List users with bot roles")]
+    Mods,
+    #[command(description = "Disable a command in this chat, eg: /disable kickme")]
+    Disable,
+    #[command(description = "Enable a disabled command")]
+    Enable,
+    #[command(description = "List disabled commands, or change how they're handled")]
+    Disabled,
+    #[command(description = "List commands that can be disabled")]
+    Disableable,
+    #[command(
+        description = "Manage a chat's settings from PM, eg: /connect @mygroup; bans, mutes and purges stay in the group"
+    )]
+    Connect,
+    #[command(description = "Stop managing a chat from PM")]
+    Disconnect,
+    #[command(description = "Show which chat your PM is connected to")]
+    Connection,
+    #[command(description = "Link this chat with another so bans and mutes apply to both")]
+    Linkchat,
+    #[command(description = "Unlink this chat from its linked chats")]
+    Unlinkchat,
+    #[command(description = "List the chats linked with this one")]
+    Linkedchats,
 }
 
 lazy_static! {
@@ -67,6 +191,10 @@ lazy_static! {
         .expect("Expected bot token")
         .parse::<i64>()
         .expect("Expected bot token to be numeric");
+    // the bot's owner can manage global settings, like the global media blocklist
+    static ref OWNER_ID: Option<i64> = std::env::var("OWNER_ID")
+        .ok()
+        .and_then(|id| id.parse::<i64>().ok());
 }
 
 #[tokio::main]
@@ -74,18 +202,53 @@ async fn main() -> anyhow::Result<()> {
     run().await
 }
 
-async fn save_details(bot: &Bot, message: &Message) -> anyhow::Result<()> {
+// returns true if this is the first time the sender spoke in the chat
+async fn save_details(bot: &Bot, message: &Message) -> anyhow::Result<bool> {
     // opportunistically save user/chat details to db
     tokio::try_join!(
-        save_user_handler(bot, message, &*POOL),
-        save_chat_handler(bot, message, &*POOL)
+        save_user_handler(bot, message, &POOL),
+        save_chat_handler(bot, message, &POOL)
     )?;
 
-    Ok(())
+    // activity references the chat, so it has to be saved afterwards
+    save_activity_handler(bot, message, &POOL).await
 }
 
 async fn answer(bot: Bot, message: Message) -> anyhow::Result<()> {
-    save_details(&bot, &message).await?;
+    let first_message = save_details(&bot, &message).await?;
+
+    // handle messages sent as a channel
+    if antichannel::handle_message(&bot, &message, &POOL).await? {
+        return Ok(());
+    }
+
+    // handle links the chat doesn't allow
+    if linkfilter::handle_message(&bot, &message, &POOL).await? {
+        return Ok(());
+    }
+
+    // handle media on the blocklist
+    if mediafilter::handle_message(&bot, &message, &POOL).await? {
+        return Ok(());
+    }
+
+    // handle messages written in filtered scripts
+    if scriptfilter::handle_message(&bot, &message, &POOL).await? {
+        return Ok(());
+    }
+
+    // handle the same message being posted over and over
+    if antidupe::handle_message(&bot, &message, &POOL).await? {
+        return Ok(());
+    }
+
+    // handle new members
+    if message.new_chat_members().is_some() {
+        antiraid::handle_joins(&bot, &message, &POOL).await?;
+        namefilter::handle_joins(&bot, &message, &POOL).await?;
+    } else if first_message {
+        namefilter::check_sender(&bot, &message, &POOL).await?;
+    }
 
     // check if update contains any text
     let text = message.text();
@@ -99,70 +262,306 @@ async fn answer(bot: Bot, message: Message) -> anyhow::Result<()> {
         && unwrapped_text.split_whitespace().count() < 2
         && unwrapped_text != "#"
     {
-        filters::get_note(&bot, &message, false, &*POOL).await?;
+        filters::get_note(&bot, &message, false, &POOL).await?;
+    }
+
+    // handle @admin mentions
+    if message.reply_to_message().is_some()
+        && !unwrapped_text.starts_with('/')
+        && unwrapped_text
+            .split_whitespace()
+            .any(|word| matches!(word.to_lowercase().as_str(), "@admin" | "@admins"))
+    {
+        reporting::report(&bot, &message, &POOL).await?;
     }
 
     let cmd = Command::parse(text.unwrap(), "rust_tgbot").ok();
 
-    if cmd.is_some() {
-        match cmd.unwrap() {
-            Command::Help => {
-                bot.send_chat_action(message.chat.id, ChatAction::Typing)
-                    .await?;
-                bot.send_message(message.chat.id, Command::descriptions())
-                    .await?;
-            }
-            Command::Id => {
-                misc::handle_id(&bot, &message, &*POOL).await?;
-            }
-            Command::Ban => {
-                banning::ban(&bot, &message, false, &*POOL).await?;
-            }
-            Command::Tban => {
-                banning::ban(&bot, &message, true, &*POOL).await?;
-            }
-            Command::Kick => {
-                banning::kick(&bot, &message, &*POOL).await?;
-            }
-            Command::Kickme => {
-                banning::kickme(&bot, &message).await?;
-            }
-            Command::Unban => {
-                banning::unban(&bot, &message, &*POOL).await?;
-            }
-            Command::Mute => {
-                muting::mute(&bot, &message, false, &*POOL).await?;
-            }
-            Command::Tmute => {
-                muting::mute(&bot, &message, true, &*POOL).await?;
-            }
-            Command::Unmute => {
-                muting::unmute(&bot, &message, &*POOL).await?;
-            }
-            Command::Promote => {
-                admin::promote(&bot, &message, &*POOL).await?;
-            }
-            Command::Demote => {
-                admin::demote(&bot, &message, &*POOL).await?;
-            }
-            Command::Pin(mode) => {
-                admin::pin(&bot, &message, mode).await?;
-            }
-            Command::Invitelink => {
-                admin::invite(&bot, &message).await?;
-            }
-            Command::Save => {
-                filters::save_note(&bot, &message, &*POOL).await?;
-            }
-            Command::Get => {
-                filters::get_note(&bot, &message, true, &*POOL).await?;
+    // commands can be disabled per chat
+    if cmd.is_some() && disabling::is_disabled(&bot, &message, &POOL).await? {
+        return Ok(());
+    }
+
+    if let Some(cmd) = cmd {
+        // anonymous admins have to show who they are before their command runs
+        if anonymous::request_verification(&bot, &message).await? {
+            return Ok(());
+        }
+
+        handle_command(&bot, &message, cmd).await?;
+    }
+
+    Ok(())
+}
+
+async fn handle_command(bot: &Bot, message: &Message, cmd: Command) -> anyhow::Result<()> {
+    match cmd {
+        Command::Help => {
+            bot.send_chat_action(message.chat.id, ChatAction::Typing)
+                .await?;
+            bot.send_message(message.chat.id, Command::descriptions())
+                .await?;
+        }
+        Command::Id => {
+            misc::handle_id(bot, message, &POOL).await?;
+        }
+        Command::Ban => {
+            banning::ban(bot, message, false, &POOL).await?;
+        }
+        Command::Tban => {
+            banning::ban(bot, message, true, &POOL).await?;
+        }
+        Command::Kick => {
+            banning::kick(bot, message, &POOL).await?;
+        }
+        Command::Kickme => {
+            banning::kickme(bot, message).await?;
+        }
+        Command::Unban => {
+            banning::unban(bot, message, &POOL).await?;
+        }
+        Command::Mute => {
+            muting::mute(bot, message, false, &POOL).await?;
+        }
+        Command::Tmute => {
+            muting::mute(bot, message, true, &POOL).await?;
+        }
+        Command::Unmute => {
+            muting::unmute(bot, message, &POOL).await?;
+        }
+        Command::Promote => {
+            admin::promote(bot, message, &POOL).await?;
+        }
+        Command::Demote => {
+            admin::demote(bot, message, &POOL).await?;
+        }
+        Command::Title => {
+            admin::title(bot, message, &POOL).await?;
+        }
+        Command::Presets => {
+            admin::promote_presets(bot, message, &POOL).await?;
+        }
+        Command::Pin(mode) => {
+            admin::pin(bot, message, mode).await?;
+        }
+        Command::Invitelink => {
+            admin::invite(bot, message).await?;
+        }
+        Command::Admincache => {
+            admin::admin_cache(bot, message).await?;
+        }
+        Command::Adminlist => {
+            admininfo::admin_list(bot, message, &POOL).await?;
+        }
+        Command::Perms => {
+            admininfo::user_perms(bot, message, &POOL).await?;
+        }
+        Command::Botperms => {
+            admininfo::bot_perms(bot, message, &POOL).await?;
+        }
+        Command::Save => {
+            filters::save_note(bot, message, &POOL).await?;
+        }
+        Command::Get => {
+            filters::get_note(bot, message, true, &POOL).await?;
+        }
+        Command::Purge => {
+            purging::purge(bot, message, false).await?;
+        }
+        Command::Spurge => {
+            purging::purge(bot, message, true).await?;
+        }
+        Command::Del => {
+            purging::delete(bot, message).await?;
+        }
+        Command::Purgefrom => {
+            purging::purge_from(bot, message).await?;
+        }
+        Command::Purgeto => {
+            purging::purge_to(bot, message).await?;
+        }
+        Command::Report => {
+            reporting::report(bot, message, &POOL).await?;
+        }
+        Command::Reports => {
+            reporting::toggle_reports(bot, message, &POOL).await?;
+        }
+        Command::Approve => {
+            approvals::approve(bot, message, &POOL).await?;
+        }
+        Command::Unapprove => {
+            approvals::unapprove(bot, message, &POOL).await?;
+        }
+        Command::Approved => {
+            approvals::approved(bot, message, &POOL).await?;
+        }
+        Command::Unapproveall => {
+            approvals::unapprove_all(bot, message, &POOL).await?;
+        }
+        Command::Nightmode => {
+            nightmode::night_mode(bot, message, &POOL).await?;
+        }
+        Command::Antiraid => {
+            antiraid::antiraid(bot, message, &POOL).await?;
+        }
+        Command::Restrict => {
+            muting::restrict(bot, message, &POOL).await?;
+        }
+        Command::Unrestrict => {
+            muting::unrestrict(bot, message, &POOL).await?;
+        }
+        Command::Appeal => {
+            appeals::appeal(bot, message, &POOL).await?;
+        }
+        Command::Settimezone => {
+            misc::set_timezone(bot, message, &POOL).await?;
+        }
+        Command::Inactive => {
+            pruning::inactive(bot, message, &POOL).await?;
+        }
+        Command::Namefilter => {
+            namefilter::name_filter(bot, message, &POOL).await?;
+        }
+        Command::Antichannel => {
+            antichannel::antichannel(bot, message, &POOL).await?;
+        }
+        Command::Linkmode => {
+            linkfilter::link_mode(bot, message, &POOL).await?;
+        }
+        Command::Allowdomain => {
+            linkfilter::manage_domains(bot, message, true, &POOL).await?;
+        }
+        Command::Blockdomain => {
+            linkfilter::manage_domains(bot, message, false, &POOL).await?;
+        }
+        Command::Scriptfilter => {
+            scriptfilter::script_filter(bot, message, &POOL).await?;
+        }
+        Command::Blockmedia => {
+            mediafilter::block_media(bot, message, &POOL).await?;
+        }
+        Command::Unblockmedia => {
+            mediafilter::unblock_media(bot, message, &POOL).await?;
+        }
+        Command::Antidupe => {
+            antidupe::antidupe(bot, message, &POOL).await?;
+        }
+        Command::Addmod => {
+            moderators::add_mod(bot, message, &POOL).await?;
+        }
+        Command::Rmmod => {
+            moderators::remove_mod(bot, message, &POOL).await?;
+        }
+        Command::Mods => {
+            moderators::list_mods(bot, message, &POOL).await?;
+        }
+        Command::Disable => {
+            disabling::toggle_commands(bot, message, true, &POOL).await?;
+        }
+        Command::Enable => {
+            disabling::toggle_commands(bot, message, false, &POOL).await?;
+        }
+        Command::Disabled => {
+            disabling::disabled(bot, message, &POOL).await?;
+        }
+        Command::Disableable => {
+            disabling::disableable(bot, message).await?;
+        }
+        Command::Connect => {
+            connection::connect(bot, message, &POOL).await?;
+        }
+        Command::Disconnect => {
+            connection::disconnect(bot, message, &POOL).await?;
+        }
+        Command::Connection => {
+            connection::connection(bot, message, &POOL).await?;
+        }
+        Command::Linkchat => {
+            linking::link_chat(bot, message, &POOL).await?;
+        }
+        Command::Unlinkchat => {
+            linking::unlink_chat(bot, message, &POOL).await?;
+        }
+        Command::Linkedchats => {
+            linking::linked_chats(bot, message, &POOL).await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_callback(bot: Bot, query: CallbackQuery) -> anyhow::Result<()> {
+    let data = match &query.data {
+        Some(data) => data,
+        None => return Ok(()),
+    };
+
+    // callback data is prefixed with the module that handles it
+    match data.split(':').next() {
+        Some("report") => reporting::handle_callback(&bot, &query, &POOL).await?,
+        Some("approval") => approvals::handle_callback(&bot, &query, &POOL).await?,
+        Some("antiraid") => antiraid::handle_callback(&bot, &query, &POOL).await?,
+        Some("appeal") => appeals::handle_callback(&bot, &query, &POOL).await?,
+        Some("inactive") => pruning::handle_callback(&bot, &query, &POOL).await?,
+        Some("anonadmin") => {
+            if let Some(message) = anonymous::handle_callback(&bot, &query).await? {
+                let text = message.text().unwrap_or_default();
+                if let Ok(cmd) = Command::parse(text, "rust_tgbot") {
+                    handle_command(&bot, &message, cmd).await?;
+                }
             }
         }
+        _ => {
+            bot.answer_callback_query(query.id).await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_chat_member(update: ChatMemberUpdated) -> anyhow::Result<()> {
+    // promotions, demotions and changed rights make the cached admin list stale
+    let is_admin = |member: &ChatMember| {
+        matches!(
+            member.status(),
+            ChatMemberStatus::Administrator | ChatMemberStatus::Owner
+        )
+    };
+    if is_admin(&update.old_chat_member) || is_admin(&update.new_chat_member) {
+        admincache::invalidate(update.chat.id);
     }
 
     Ok(())
 }
 
+// the dispatcher asks for the default updates, which leave out chat member updates
+struct WithChatMembers<L>(L);
+
+impl<L: UpdateListener<E>, E> UpdateListener<E> for WithChatMembers<L> {
+    type StopToken = L::StopToken;
+
+    fn stop_token(&mut self) -> Self::StopToken {
+        self.0.stop_token()
+    }
+
+    fn hint_allowed_updates(&mut self, _: &mut dyn Iterator<Item = AllowedUpdate>) {
+        self.0
+            .hint_allowed_updates(&mut ALLOWED_UPDATES.iter().cloned());
+    }
+
+    fn timeout_hint(&self) -> Option<Duration> {
+        self.0.timeout_hint()
+    }
+}
+
+impl<'a, L: AsUpdateStream<'a, E>, E> AsUpdateStream<'a, E> for WithChatMembers<L> {
+    type Stream = L::Stream;
+
+    fn as_stream(&'a mut self) -> Self::Stream {
+        self.0.as_stream()
+    }
+}
+
 async fn run() -> anyhow::Result<()> {
     // load env config
     dotenv()?;
@@ -174,12 +573,26 @@ async fn run() -> anyhow::Result<()> {
         .parse_mode(ParseMode::Html)
         .auto_send();
 
-    let handler = dptree::entry().branch(Update::filter_message().endpoint(answer));
+    // lock and unlock chats on schedule
+    tokio::spawn(nightmode::run_scheduler(bot.clone(), &POOL));
+
+    // end raids that have run their course
+    tokio::spawn(antiraid::run_scheduler(bot.clone(), &POOL));
+
+    let handler = dptree::entry()
+        .branch(Update::filter_message().endpoint(answer))
+        .branch(Update::filter_callback_query().endpoint(handle_callback))
+        .branch(Update::filter_chat_member().endpoint(handle_chat_member))
+        .branch(Update::filter_my_chat_member().endpoint(handle_chat_member));
 
+    let listener = WithChatMembers(update_listeners::polling_default(bot.clone()).await);
     Dispatcher::builder(bot, handler)
         .build()
         .setup_ctrlc_handler()
-        .dispatch()
+        .dispatch_with_listener(
+            listener,
+            LoggingErrorHandler::with_custom_text("An error from the update listener"),
+        )
         .await;
 
     log::info!("Shutting down marvin... goodbye!");
@@ -0,0 +1,65 @@
+use chrono::{DateTime, Utc};
+use sqlx::{Pool, Postgres};
+
+// returns true if this is the first time the user was seen in the chat
+pub async fn set_last_seen(
+    chat_id: i64,
+    user_id: i64,
+    last_seen: DateTime<Utc>,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<bool> {
+    let res = sqlx::query!(
+        r#"
+        INSERT into activity (chat_id, user_id, last_seen) VALUES ($1, $2, $3)
+        ON CONFLICT (chat_id, user_id) DO
+        UPDATE SET last_seen = excluded.last_seen
+        WHERE activity.last_seen < excluded.last_seen
+        RETURNING (xmax = 0) as "inserted!"
+        "#,
+        chat_id,
+        user_id,
+        last_seen
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(res.map_or(false, |res| res.inserted))
+}
+
+pub async fn forget_user(chat_id: i64, user_id: i64, pool: &Pool<Postgres>) -> anyhow::Result<()> {
+    sqlx::query!(
+        "DELETE FROM activity WHERE chat_id = $1 AND user_id = $2",
+        chat_id,
+        user_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// users who haven't spoken since the cutoff, leaving out approved users
+pub async fn get_inactive_users(
+    chat_id: i64,
+    cutoff: DateTime<Utc>,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<Vec<(i64, Option<String>, DateTime<Utc>)>> {
+    let users = sqlx::query!(
+        r#"
+        SELECT activity.user_id, users.full_name as "full_name?", activity.last_seen FROM activity
+        LEFT JOIN users ON users.user_id = activity.user_id
+        WHERE activity.chat_id = $1 AND activity.last_seen < $2
+        AND NOT EXISTS (
+            SELECT 1 FROM approvals
+            WHERE approvals.chat_id = activity.chat_id AND approvals.user_id = activity.user_id
+        )
+        ORDER BY activity.last_seen
+        "#,
+        chat_id,
+        cutoff
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(users
+        .into_iter()
+        .map(|u| (u.user_id, u.full_name, u.last_seen))
+        .collect())
+}
@@ -0,0 +1,32 @@
+use sqlx::{Pool, Postgres};
+
+pub async fn set_antichannel(
+    chat_id: i64,
+    enabled: bool,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into antichannel (chat_id, enabled) VALUES ($1, $2)
+        ON CONFLICT (chat_id) DO
+        UPDATE SET enabled = excluded.enabled
+        "#,
+        chat_id,
+        enabled
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_antichannel(chat_id: i64, pool: &Pool<Postgres>) -> anyhow::Result<bool> {
+    let settings = sqlx::query!(
+        "SELECT enabled FROM antichannel WHERE chat_id = $1",
+        chat_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    // channels are allowed to post unless an admin turned this on
+    Ok(settings.map(|s| s.enabled).unwrap_or(false))
+}
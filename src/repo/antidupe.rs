@@ -0,0 +1,42 @@
+use sqlx::{Pool, Postgres};
+
+use crate::entities::AntiDupe;
+
+pub async fn get_antidupe(chat_id: i64, pool: &Pool<Postgres>) -> anyhow::Result<AntiDupe> {
+    let antidupe = sqlx::query_as!(
+        AntiDupe,
+        "SELECT * FROM antidupe WHERE chat_id = $1",
+        chat_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    // fall back to the table defaults for chats that never configured duplicate detection
+    Ok(antidupe.unwrap_or(AntiDupe {
+        chat_id,
+        repeat_threshold: 0,
+        user_threshold: 0,
+        window_duration: 60,
+        mute_duration: 3600,
+    }))
+}
+
+pub async fn set_antidupe(antidupe: &AntiDupe, pool: &Pool<Postgres>) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into antidupe (chat_id, repeat_threshold, user_threshold, window_duration, mute_duration)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (chat_id) DO
+        UPDATE SET repeat_threshold = excluded.repeat_threshold, user_threshold = excluded.user_threshold,
+        window_duration = excluded.window_duration, mute_duration = excluded.mute_duration
+        "#,
+        antidupe.chat_id,
+        antidupe.repeat_threshold,
+        antidupe.user_threshold,
+        antidupe.window_duration,
+        antidupe.mute_duration
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,124 @@
+use chrono::{DateTime, Utc};
+use sqlx::{Pool, Postgres};
+
+use crate::entities::AntiRaid;
+
+pub async fn get_antiraid(chat_id: i64, pool: &Pool<Postgres>) -> anyhow::Result<AntiRaid> {
+    let antiraid = sqlx::query_as!(
+        AntiRaid,
+        "SELECT * FROM antiraid WHERE chat_id = $1",
+        chat_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    // fall back to the table defaults for chats that never configured anti-raid
+    Ok(antiraid.unwrap_or(AntiRaid {
+        chat_id,
+        threshold: 0,
+        ban_duration: 3600,
+        raid_duration: 21600,
+        raid_until: None,
+    }))
+}
+
+pub async fn set_threshold(
+    chat_id: i64,
+    threshold: i32,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into antiraid (chat_id, threshold) VALUES ($1, $2)
+        ON CONFLICT (chat_id) DO
+        UPDATE SET threshold = excluded.threshold
+        "#,
+        chat_id,
+        threshold
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn set_ban_duration(
+    chat_id: i64,
+    ban_duration: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into antiraid (chat_id, ban_duration) VALUES ($1, $2)
+        ON CONFLICT (chat_id) DO
+        UPDATE SET ban_duration = excluded.ban_duration
+        "#,
+        chat_id,
+        ban_duration
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn set_raid_until(
+    chat_id: i64,
+    raid_until: Option<DateTime<Utc>>,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into antiraid (chat_id, raid_until) VALUES ($1, $2)
+        ON CONFLICT (chat_id) DO
+        UPDATE SET raid_until = excluded.raid_until
+        "#,
+        chat_id,
+        raid_until
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_expired_raids(pool: &Pool<Postgres>) -> anyhow::Result<Vec<AntiRaid>> {
+    let raids = sqlx::query_as!(
+        AntiRaid,
+        "SELECT * FROM antiraid WHERE raid_until IS NOT NULL AND raid_until <= now()"
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(raids)
+}
+
+pub async fn add_raider(chat_id: i64, user_id: i64, pool: &Pool<Postgres>) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into raiders (chat_id, user_id) VALUES ($1, $2)
+        ON CONFLICT (chat_id, user_id) DO NOTHING
+        "#,
+        chat_id,
+        user_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn count_raiders(chat_id: i64, pool: &Pool<Postgres>) -> anyhow::Result<i64> {
+    let count = sqlx::query!(
+        r#"SELECT COUNT(*) as "count!" FROM raiders WHERE chat_id = $1"#,
+        chat_id
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(count.count)
+}
+
+pub async fn take_raiders(chat_id: i64, pool: &Pool<Postgres>) -> anyhow::Result<Vec<i64>> {
+    let raiders = sqlx::query!(
+        "DELETE FROM raiders WHERE chat_id = $1 RETURNING user_id",
+        chat_id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(raiders.into_iter().map(|r| r.user_id).collect())
+}
@@ -0,0 +1,97 @@
+use sqlx::{Pool, Postgres};
+
+use crate::entities::Appeal;
+
+pub async fn create_appeal(
+    chat_id: i64,
+    user_id: i64,
+    reason: Option<String>,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<i32> {
+    let appeal_id = sqlx::query_scalar!(
+        r#"
+        INSERT into appeals (chat_id, user_id, reason) VALUES ($1, $2, $3)
+        RETURNING appeal_id
+        "#,
+        chat_id,
+        user_id,
+        reason
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(appeal_id)
+}
+
+pub async fn get_appeal(appeal_id: i32, pool: &Pool<Postgres>) -> anyhow::Result<Option<Appeal>> {
+    let appeal = sqlx::query_as!(
+        Appeal,
+        "SELECT * FROM appeals WHERE appeal_id = $1",
+        appeal_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(appeal)
+}
+
+// count a user's appeals in a chat, and whether any of them are still waiting on a decision;
+// appeals nobody decided on within a week no longer hold up new ones
+pub async fn count_appeals(
+    chat_id: i64,
+    user_id: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<(i64, bool)> {
+    let res = sqlx::query!(
+        r#"
+        SELECT COUNT(*) as "count!", COUNT(*) FILTER (WHERE status = 'pending' AND created_at > now() - interval '7 days') as "pending!"
+        FROM appeals WHERE chat_id = $1 AND user_id = $2
+        "#,
+        chat_id,
+        user_id
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok((res.count, res.pending > 0))
+}
+
+// decide a pending appeal, returning false if it was already decided
+pub async fn decide_appeal(
+    appeal_id: i32,
+    status: &str,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<bool> {
+    let res = sqlx::query!(
+        "UPDATE appeals SET status = $2 WHERE appeal_id = $1 AND status = 'pending'",
+        appeal_id,
+        status
+    )
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+// put a decided appeal back up for a decision, when acting on it didn't work out
+pub async fn reopen_appeal(appeal_id: i32, pool: &Pool<Postgres>) -> anyhow::Result<()> {
+    sqlx::query!(
+        "UPDATE appeals SET status = 'pending' WHERE appeal_id = $1",
+        appeal_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// withdraw a user's pending appeals in a chat, once there's nothing left to decide
+pub async fn withdraw_appeals(
+    chat_id: i64,
+    user_id: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        "UPDATE appeals SET status = 'withdrawn' WHERE chat_id = $1 AND user_id = $2 AND status = 'pending'",
+        chat_id,
+        user_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
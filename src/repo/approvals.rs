@@ -0,0 +1,72 @@
+use sqlx::{Pool, Postgres};
+
+pub async fn approve_user(chat_id: i64, user_id: i64, pool: &Pool<Postgres>) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into approvals (chat_id, user_id) VALUES ($1, $2)
+        ON CONFLICT (chat_id, user_id) DO NOTHING
+        "#,
+        chat_id,
+        user_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn unapprove_user(
+    chat_id: i64,
+    user_id: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<bool> {
+    let res = sqlx::query!(
+        "DELETE FROM approvals WHERE chat_id = $1 AND user_id = $2",
+        chat_id,
+        user_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn unapprove_all(chat_id: i64, pool: &Pool<Postgres>) -> anyhow::Result<u64> {
+    let res = sqlx::query!("DELETE FROM approvals WHERE chat_id = $1", chat_id)
+        .execute(pool)
+        .await?;
+    Ok(res.rows_affected())
+}
+
+pub async fn is_approved(
+    chat_id: i64,
+    user_id: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<bool> {
+    let approval = sqlx::query!(
+        "SELECT user_id FROM approvals WHERE chat_id = $1 AND user_id = $2",
+        chat_id,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(approval.is_some())
+}
+
+pub async fn get_approved_users(
+    chat_id: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<Vec<(i64, Option<String>)>> {
+    let approvals = sqlx::query!(
+        r#"
+        SELECT approvals.user_id, users.full_name as "full_name?" FROM approvals
+        LEFT JOIN users ON users.user_id = approvals.user_id
+        WHERE approvals.chat_id = $1
+        "#,
+        chat_id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(approvals
+        .into_iter()
+        .map(|a| (a.user_id, a.full_name))
+        .collect())
+}
@@ -0,0 +1,37 @@
+use sqlx::{Pool, Postgres};
+
+pub async fn set_connection(
+    user_id: i64,
+    chat_id: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into connections (user_id, chat_id) VALUES ($1, $2)
+        ON CONFLICT (user_id) DO
+        UPDATE SET chat_id = excluded.chat_id
+        "#,
+        user_id,
+        chat_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn remove_connection(user_id: i64, pool: &Pool<Postgres>) -> anyhow::Result<bool> {
+    let res = sqlx::query!("DELETE FROM connections WHERE user_id = $1", user_id)
+        .execute(pool)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn get_connection(user_id: i64, pool: &Pool<Postgres>) -> anyhow::Result<Option<i64>> {
+    let chat_id = sqlx::query_scalar!(
+        "SELECT chat_id FROM connections WHERE user_id = $1",
+        user_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(chat_id)
+}
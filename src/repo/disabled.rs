@@ -0,0 +1,100 @@
+use sqlx::{Pool, Postgres};
+
+use crate::entities::CommandSettings;
+
+pub async fn disable_command(
+    chat_id: i64,
+    command: &str,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        "INSERT into disabled_commands (chat_id, command) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+        chat_id,
+        command
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn enable_command(
+    chat_id: i64,
+    command: &str,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<bool> {
+    let res = sqlx::query!(
+        "DELETE FROM disabled_commands WHERE chat_id = $1 AND command = $2",
+        chat_id,
+        command
+    )
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn is_command_disabled(
+    chat_id: i64,
+    command: &str,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<bool> {
+    let disabled = sqlx::query!(
+        "SELECT command FROM disabled_commands WHERE chat_id = $1 AND command = $2",
+        chat_id,
+        command
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(disabled.is_some())
+}
+
+pub async fn get_disabled_commands(
+    chat_id: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<Vec<String>> {
+    let commands = sqlx::query_scalar!(
+        "SELECT command FROM disabled_commands WHERE chat_id = $1 ORDER BY command",
+        chat_id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(commands)
+}
+
+pub async fn get_command_settings(
+    chat_id: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<CommandSettings> {
+    let settings = sqlx::query_as!(
+        CommandSettings,
+        "SELECT * FROM command_settings WHERE chat_id = $1",
+        chat_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    // fall back to the table defaults for chats that never changed them
+    Ok(settings.unwrap_or(CommandSettings {
+        chat_id,
+        delete_disabled: false,
+        exempt_admins: true,
+    }))
+}
+
+pub async fn set_command_settings(
+    settings: &CommandSettings,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into command_settings (chat_id, delete_disabled, exempt_admins) VALUES ($1, $2, $3)
+        ON CONFLICT (chat_id) DO
+        UPDATE SET delete_disabled = excluded.delete_disabled, exempt_admins = excluded.exempt_admins
+        "#,
+        settings.chat_id,
+        settings.delete_disabled,
+        settings.exempt_admins
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
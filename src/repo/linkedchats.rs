@@ -0,0 +1,98 @@
+use sqlx::{Pool, Postgres};
+
+// link two chats, merging their groups if the other chat is already linked elsewhere
+pub async fn link_chats(chat_id: i64, other_id: i64, pool: &Pool<Postgres>) -> anyhow::Result<()> {
+    let mut tx = pool.begin().await?;
+
+    // a chat that isn't linked yet starts a new group
+    sqlx::query!(
+        r#"
+        INSERT into linked_chats (chat_id, link_id) VALUES ($1, nextval('linked_chats_link_id_seq'))
+        ON CONFLICT (chat_id) DO NOTHING
+        "#,
+        chat_id
+    )
+    .execute(&mut tx)
+    .await?;
+
+    let link_id = sqlx::query_scalar!(
+        "SELECT link_id FROM linked_chats WHERE chat_id = $1",
+        chat_id
+    )
+    .fetch_one(&mut tx)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        UPDATE linked_chats SET link_id = $1
+        WHERE link_id = (SELECT link_id FROM linked_chats WHERE chat_id = $2)
+        "#,
+        link_id,
+        other_id
+    )
+    .execute(&mut tx)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT into linked_chats (chat_id, link_id) VALUES ($1, $2)
+        ON CONFLICT (chat_id) DO NOTHING
+        "#,
+        other_id,
+        link_id
+    )
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+pub async fn unlink_chat(chat_id: i64, pool: &Pool<Postgres>) -> anyhow::Result<bool> {
+    let link_id = sqlx::query_scalar!(
+        "DELETE FROM linked_chats WHERE chat_id = $1 RETURNING link_id",
+        chat_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    let link_id = match link_id {
+        Some(link_id) => link_id,
+        None => return Ok(false),
+    };
+
+    // a chat left on its own isn't linked to anything anymore
+    sqlx::query!(
+        r#"
+        DELETE FROM linked_chats
+        WHERE link_id = $1 AND (SELECT COUNT(*) FROM linked_chats WHERE link_id = $1) = 1
+        "#,
+        link_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(true)
+}
+
+// the other chats linked with a chat, along with their names
+pub async fn get_linked_chats(
+    chat_id: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<Vec<(i64, Option<String>)>> {
+    let chats = sqlx::query!(
+        r#"
+        SELECT linked.chat_id, chats.chat_name FROM linked_chats linked
+        INNER JOIN linked_chats own ON own.link_id = linked.link_id
+        INNER JOIN chats ON chats.chat_id = linked.chat_id
+        WHERE own.chat_id = $1 AND linked.chat_id != $1
+        ORDER BY linked.chat_id
+        "#,
+        chat_id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(chats
+        .into_iter()
+        .map(|c| (c.chat_id, c.chat_name))
+        .collect())
+}
@@ -0,0 +1,97 @@
+use sqlx::{Pool, Postgres};
+
+use crate::entities::LinkSettings;
+
+pub async fn get_link_settings(
+    chat_id: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<LinkSettings> {
+    let settings = sqlx::query_as!(
+        LinkSettings,
+        "SELECT * FROM link_settings WHERE chat_id = $1",
+        chat_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    // links aren't filtered unless an admin turned it on
+    Ok(settings.unwrap_or(LinkSettings {
+        chat_id,
+        mode: "off".to_owned(),
+        action: None,
+    }))
+}
+
+pub async fn set_link_settings(
+    chat_id: i64,
+    mode: &str,
+    action: Option<String>,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into link_settings (chat_id, mode, action) VALUES ($1, $2, $3)
+        ON CONFLICT (chat_id) DO
+        UPDATE SET mode = excluded.mode, action = excluded.action
+        "#,
+        chat_id,
+        mode,
+        action
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn set_domain(
+    chat_id: i64,
+    domain: &str,
+    allowed: bool,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into link_domains (chat_id, domain, allowed) VALUES ($1, $2, $3)
+        ON CONFLICT (chat_id, domain) DO
+        UPDATE SET allowed = excluded.allowed
+        "#,
+        chat_id,
+        domain,
+        allowed
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn remove_domain(
+    chat_id: i64,
+    domain: &str,
+    allowed: bool,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<bool> {
+    let res = sqlx::query!(
+        "DELETE FROM link_domains WHERE chat_id = $1 AND domain = $2 AND allowed = $3",
+        chat_id,
+        domain,
+        allowed
+    )
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn get_domains(
+    chat_id: i64,
+    allowed: bool,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<Vec<String>> {
+    let domains = sqlx::query_scalar!(
+        "SELECT domain FROM link_domains WHERE chat_id = $1 AND allowed = $2 ORDER BY domain",
+        chat_id,
+        allowed
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(domains)
+}
@@ -0,0 +1,115 @@
+use sqlx::{Pool, Postgres};
+
+use crate::entities::BlockedMedia;
+
+// block media in a chat, or everywhere if no chat is given
+pub async fn block_media(
+    chat_id: Option<i64>,
+    media: &BlockedMedia,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    match chat_id {
+        Some(chat_id) => {
+            sqlx::query!(
+                r#"
+                INSERT into blocked_media (chat_id, file_unique_id, media_type, action) VALUES ($1, $2, $3, $4)
+                ON CONFLICT (chat_id, file_unique_id) DO
+                UPDATE SET action = excluded.action
+                "#,
+                chat_id,
+                media.file_unique_id,
+                media.media_type,
+                media.action
+            )
+            .execute(pool)
+            .await?;
+        }
+        None => {
+            sqlx::query!(
+                r#"
+                INSERT into global_blocked_media (file_unique_id, media_type, action) VALUES ($1, $2, $3)
+                ON CONFLICT (file_unique_id) DO
+                UPDATE SET action = excluded.action
+                "#,
+                media.file_unique_id,
+                media.media_type,
+                media.action
+            )
+            .execute(pool)
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn unblock_media(
+    chat_id: Option<i64>,
+    file_unique_id: &str,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<bool> {
+    let res = match chat_id {
+        Some(chat_id) => {
+            sqlx::query!(
+                "DELETE FROM blocked_media WHERE chat_id = $1 AND file_unique_id = $2",
+                chat_id,
+                file_unique_id
+            )
+            .execute(pool)
+            .await?
+        }
+        None => {
+            sqlx::query!(
+                "DELETE FROM global_blocked_media WHERE file_unique_id = $1",
+                file_unique_id
+            )
+            .execute(pool)
+            .await?
+        }
+    };
+    Ok(res.rows_affected() > 0)
+}
+
+// find the first of some files that's blocked in a chat, chat entries take precedence over global ones
+pub async fn find_blocked_media(
+    chat_id: i64,
+    file_unique_ids: &[String],
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<Option<BlockedMedia>> {
+    let media = sqlx::query_as!(
+        BlockedMedia,
+        r#"
+        SELECT file_unique_id as "file_unique_id!", media_type as "media_type!", action FROM (
+            SELECT file_unique_id, media_type, action, 0 as priority FROM blocked_media
+            WHERE chat_id = $1 AND file_unique_id = ANY($2)
+            UNION ALL
+            SELECT file_unique_id, media_type, action, 1 as priority FROM global_blocked_media
+            WHERE file_unique_id = ANY($2)
+        ) AS blocked
+        ORDER BY priority
+        LIMIT 1
+        "#,
+        chat_id,
+        file_unique_ids
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(media)
+}
+
+// number of files blocked in a chat and globally
+pub async fn count_blocked_media(
+    chat_id: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<(i64, i64)> {
+    let counts = sqlx::query!(
+        r#"
+        SELECT
+            (SELECT COUNT(*) FROM blocked_media WHERE chat_id = $1) as "chat!",
+            (SELECT COUNT(*) FROM global_blocked_media) as "global!"
+        "#,
+        chat_id
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok((counts.chat, counts.global))
+}
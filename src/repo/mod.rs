@@ -1,3 +1,22 @@
+pub mod activity;
+pub mod antichannel;
+pub mod antidupe;
+pub mod antiraid;
+pub mod appeals;
+pub mod approvals;
 pub mod chats;
+pub mod connections;
+pub mod disabled;
 pub mod filters;
+pub mod linkedchats;
+pub mod links;
+pub mod media;
+pub mod namefilters;
+pub mod nightmode;
+pub mod promotepresets;
+pub mod reports;
+pub mod restrictions;
+pub mod roles;
+pub mod scriptfilters;
+pub mod timezones;
 pub mod users;
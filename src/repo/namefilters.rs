@@ -0,0 +1,53 @@
+use sqlx::{Pool, Postgres};
+
+use crate::entities::NameFilter;
+
+pub async fn add_name_filter(
+    chat_id: i64,
+    pattern: &str,
+    action: &str,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into name_filters (chat_id, pattern, action) VALUES ($1, $2, $3)
+        ON CONFLICT (chat_id, pattern) DO
+        UPDATE SET action = excluded.action
+        "#,
+        chat_id,
+        pattern,
+        action
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn remove_name_filter(
+    chat_id: i64,
+    pattern: &str,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<bool> {
+    let res = sqlx::query!(
+        "DELETE FROM name_filters WHERE chat_id = $1 AND pattern = $2",
+        chat_id,
+        pattern
+    )
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn get_name_filters(
+    chat_id: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<Vec<NameFilter>> {
+    let filters = sqlx::query_as!(
+        NameFilter,
+        "SELECT * FROM name_filters WHERE chat_id = $1 ORDER BY pattern",
+        chat_id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(filters)
+}
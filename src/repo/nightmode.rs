@@ -0,0 +1,65 @@
+use sqlx::{Pool, Postgres};
+
+use crate::entities::NightMode;
+
+pub async fn set_night_mode(night_mode: &NightMode, pool: &Pool<Postgres>) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into night_mode (chat_id, start_time, end_time, timezone) VALUES ($1, $2, $3, $4)
+        ON CONFLICT (chat_id) DO
+        UPDATE SET (start_time, end_time, timezone) = (excluded.start_time, excluded.end_time, excluded.timezone)
+        "#,
+        night_mode.chat_id,
+        night_mode.start_time,
+        night_mode.end_time,
+        night_mode.timezone,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_night_mode(
+    chat_id: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<Option<NightMode>> {
+    let night_mode = sqlx::query_as!(
+        NightMode,
+        "SELECT * FROM night_mode WHERE chat_id = $1",
+        chat_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(night_mode)
+}
+
+pub async fn get_all_night_modes(pool: &Pool<Postgres>) -> anyhow::Result<Vec<NightMode>> {
+    let night_modes = sqlx::query_as!(NightMode, "SELECT * FROM night_mode")
+        .fetch_all(pool)
+        .await?;
+    Ok(night_modes)
+}
+
+pub async fn set_night_mode_active(
+    chat_id: i64,
+    active: bool,
+    saved_permissions: Option<i16>,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        "UPDATE night_mode SET active = $2, saved_permissions = $3 WHERE chat_id = $1",
+        chat_id,
+        active,
+        saved_permissions
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn delete_night_mode(chat_id: i64, pool: &Pool<Postgres>) -> anyhow::Result<()> {
+    sqlx::query!("DELETE FROM night_mode WHERE chat_id = $1", chat_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
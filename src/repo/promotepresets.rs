@@ -0,0 +1,118 @@
+use sqlx::{Pool, Postgres};
+
+use crate::entities::PromotePreset;
+
+// presets every chat starts with, until it saves its own under the same name
+fn default_presets(chat_id: i64) -> Vec<PromotePreset> {
+    vec![
+        PromotePreset {
+            chat_id,
+            name: "mod".to_owned(),
+            can_manage_chat: true,
+            can_change_info: false,
+            can_delete_messages: true,
+            can_manage_voice_chats: false,
+            can_invite_users: true,
+            can_restrict_members: true,
+            can_pin_messages: true,
+            can_promote_members: false,
+        },
+        PromotePreset {
+            chat_id,
+            name: "full".to_owned(),
+            can_manage_chat: true,
+            can_change_info: true,
+            can_delete_messages: true,
+            can_manage_voice_chats: true,
+            can_invite_users: true,
+            can_restrict_members: true,
+            can_pin_messages: true,
+            can_promote_members: true,
+        },
+    ]
+}
+
+pub async fn get_preset(
+    chat_id: i64,
+    name: &str,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<Option<PromotePreset>> {
+    let preset = sqlx::query_as!(
+        PromotePreset,
+        "SELECT * FROM promote_presets WHERE chat_id = $1 AND name = $2",
+        chat_id,
+        name
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(preset.or_else(|| {
+        default_presets(chat_id)
+            .into_iter()
+            .find(|preset| preset.name == name)
+    }))
+}
+
+pub async fn get_presets(
+    chat_id: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<Vec<PromotePreset>> {
+    let mut presets = sqlx::query_as!(
+        PromotePreset,
+        "SELECT * FROM promote_presets WHERE chat_id = $1 ORDER BY name",
+        chat_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    for preset in default_presets(chat_id) {
+        if !presets.iter().any(|saved| saved.name == preset.name) {
+            presets.push(preset);
+        }
+    }
+    presets.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(presets)
+}
+
+pub async fn set_preset(preset: &PromotePreset, pool: &Pool<Postgres>) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into promote_presets (chat_id, name, can_manage_chat, can_change_info, can_delete_messages,
+        can_manage_voice_chats, can_invite_users, can_restrict_members, can_pin_messages, can_promote_members)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        ON CONFLICT (chat_id, name) DO
+        UPDATE SET can_manage_chat = excluded.can_manage_chat, can_change_info = excluded.can_change_info,
+        can_delete_messages = excluded.can_delete_messages, can_manage_voice_chats = excluded.can_manage_voice_chats,
+        can_invite_users = excluded.can_invite_users, can_restrict_members = excluded.can_restrict_members,
+        can_pin_messages = excluded.can_pin_messages, can_promote_members = excluded.can_promote_members
+        "#,
+        preset.chat_id,
+        preset.name,
+        preset.can_manage_chat,
+        preset.can_change_info,
+        preset.can_delete_messages,
+        preset.can_manage_voice_chats,
+        preset.can_invite_users,
+        preset.can_restrict_members,
+        preset.can_pin_messages,
+        preset.can_promote_members
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn remove_preset(
+    chat_id: i64,
+    name: &str,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<bool> {
+    let res = sqlx::query!(
+        "DELETE FROM promote_presets WHERE chat_id = $1 AND name = $2",
+        chat_id,
+        name
+    )
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected() > 0)
+}
@@ -0,0 +1,91 @@
+use sqlx::{Pool, Postgres};
+
+pub async fn set_reports_enabled(
+    chat_id: i64,
+    enabled: bool,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into report_settings (chat_id, enabled) VALUES ($1, $2)
+        ON CONFLICT (chat_id) DO
+        UPDATE SET enabled = excluded.enabled
+        "#,
+        chat_id,
+        enabled
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_reports_enabled(chat_id: i64, pool: &Pool<Postgres>) -> anyhow::Result<bool> {
+    let settings = sqlx::query!(
+        "SELECT enabled FROM report_settings WHERE chat_id = $1",
+        chat_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    // reports are enabled unless an admin turned them off
+    Ok(settings.map(|s| s.enabled).unwrap_or(true))
+}
+
+// remember where a report was sent so every admin's copy can be updated later
+pub async fn add_report_copy(
+    chat_id: i64,
+    message_id: i32,
+    admin_id: i64,
+    copy_id: i32,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into report_copies (chat_id, message_id, admin_id, copy_id) VALUES ($1, $2, $3, $4)
+        ON CONFLICT (chat_id, message_id, admin_id) DO
+        UPDATE SET copy_id = excluded.copy_id
+        "#,
+        chat_id,
+        message_id,
+        admin_id,
+        copy_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// every admin's copy of a report as (admin_id, copy_id)
+pub async fn get_report_copies(
+    chat_id: i64,
+    message_id: i32,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<Vec<(i64, i32)>> {
+    let copies = sqlx::query!(
+        "SELECT admin_id, copy_id FROM report_copies WHERE chat_id = $1 AND message_id = $2",
+        chat_id,
+        message_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(copies
+        .into_iter()
+        .map(|c| (c.admin_id, c.copy_id))
+        .collect())
+}
+
+pub async fn remove_report_copies(
+    chat_id: i64,
+    message_id: i32,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        "DELETE FROM report_copies WHERE chat_id = $1 AND message_id = $2",
+        chat_id,
+        message_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
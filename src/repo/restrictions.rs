@@ -0,0 +1,52 @@
+use sqlx::{Pool, Postgres};
+
+pub async fn get_revoked_permissions(
+    chat_id: i64,
+    user_id: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<Option<i16>> {
+    let restriction = sqlx::query!(
+        "SELECT revoked_permissions FROM restrictions WHERE chat_id = $1 AND user_id = $2",
+        chat_id,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(restriction.map(|r| r.revoked_permissions))
+}
+
+pub async fn set_revoked_permissions(
+    chat_id: i64,
+    user_id: i64,
+    revoked_permissions: i16,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into restrictions (chat_id, user_id, revoked_permissions) VALUES ($1, $2, $3)
+        ON CONFLICT (chat_id, user_id) DO
+        UPDATE SET revoked_permissions = excluded.revoked_permissions
+        "#,
+        chat_id,
+        user_id,
+        revoked_permissions
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn remove_restrictions(
+    chat_id: i64,
+    user_id: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        "DELETE FROM restrictions WHERE chat_id = $1 AND user_id = $2",
+        chat_id,
+        user_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
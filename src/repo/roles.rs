@@ -0,0 +1,73 @@
+use sqlx::{Pool, Postgres};
+
+pub async fn set_role(
+    chat_id: i64,
+    user_id: i64,
+    role: &str,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into roles (chat_id, user_id, role) VALUES ($1, $2, $3)
+        ON CONFLICT (chat_id, user_id) DO
+        UPDATE SET role = excluded.role
+        "#,
+        chat_id,
+        user_id,
+        role
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn remove_role(
+    chat_id: i64,
+    user_id: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<bool> {
+    let res = sqlx::query!(
+        "DELETE FROM roles WHERE chat_id = $1 AND user_id = $2",
+        chat_id,
+        user_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn get_role(
+    chat_id: i64,
+    user_id: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<Option<String>> {
+    let role = sqlx::query_scalar!(
+        "SELECT role FROM roles WHERE chat_id = $1 AND user_id = $2",
+        chat_id,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(role)
+}
+
+pub async fn get_roles(
+    chat_id: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<Vec<(i64, String, Option<String>)>> {
+    let roles = sqlx::query!(
+        r#"
+        SELECT roles.user_id, roles.role, users.full_name as "full_name?" FROM roles
+        LEFT JOIN users ON users.user_id = roles.user_id
+        WHERE roles.chat_id = $1
+        ORDER BY roles.role, roles.user_id
+        "#,
+        chat_id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(roles
+        .into_iter()
+        .map(|r| (r.user_id, r.role, r.full_name))
+        .collect())
+}
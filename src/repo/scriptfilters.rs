@@ -0,0 +1,49 @@
+use sqlx::{Pool, Postgres};
+
+use crate::entities::ScriptFilter;
+
+pub async fn add_script_filter(filter: &ScriptFilter, pool: &Pool<Postgres>) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into script_filters (chat_id, script, threshold, action) VALUES ($1, $2, $3, $4)
+        ON CONFLICT (chat_id, script) DO
+        UPDATE SET threshold = excluded.threshold, action = excluded.action
+        "#,
+        filter.chat_id,
+        filter.script,
+        filter.threshold,
+        filter.action
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn remove_script_filter(
+    chat_id: i64,
+    script: &str,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<bool> {
+    let res = sqlx::query!(
+        "DELETE FROM script_filters WHERE chat_id = $1 AND script = $2",
+        chat_id,
+        script
+    )
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn get_script_filters(
+    chat_id: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<Vec<ScriptFilter>> {
+    let filters = sqlx::query_as!(
+        ScriptFilter,
+        "SELECT * FROM script_filters WHERE chat_id = $1 ORDER BY script",
+        chat_id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(filters)
+}
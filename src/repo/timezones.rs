@@ -0,0 +1,28 @@
+use sqlx::{Pool, Postgres};
+
+pub async fn set_timezone(
+    chat_id: i64,
+    timezone: &str,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into timezones (chat_id, timezone) VALUES ($1, $2)
+        ON CONFLICT (chat_id) DO
+        UPDATE SET timezone = excluded.timezone
+        "#,
+        chat_id,
+        timezone
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_timezone(chat_id: i64, pool: &Pool<Postgres>) -> anyhow::Result<Option<String>> {
+    let timezone =
+        sqlx::query_scalar!("SELECT timezone FROM timezones WHERE chat_id = $1", chat_id)
+            .fetch_optional(pool)
+            .await?;
+    Ok(timezone)
+}
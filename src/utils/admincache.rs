@@ -0,0 +1,63 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use lazy_static::lazy_static;
+use teloxide::{prelude2::*, types::ChatMember};
+use tokio::sync::Mutex as AsyncMutex;
+
+// how long a chat's admin list is trusted before it's fetched again
+const CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+
+struct CachedAdmins {
+    fetched_at: Instant,
+    admins: Vec<ChatMember>,
+}
+
+lazy_static! {
+    // admin lists per chat, each behind its own lock so concurrent checks share a single fetch
+    static ref ADMIN_CACHE: Mutex<HashMap<i64, Arc<AsyncMutex<Option<CachedAdmins>>>>> =
+        Mutex::new(HashMap::new());
+}
+
+// every admin of a chat, including its owner
+pub async fn get_admins(bot: &crate::Bot, chat_id: i64) -> anyhow::Result<Vec<ChatMember>> {
+    let entry = ADMIN_CACHE
+        .lock()
+        .unwrap()
+        .entry(chat_id)
+        .or_default()
+        .clone();
+    let mut cached = entry.lock().await;
+
+    if let Some(cached) = cached.as_ref() {
+        if cached.fetched_at.elapsed() < CACHE_TTL {
+            return Ok(cached.admins.clone());
+        }
+    }
+
+    let admins = bot.get_chat_administrators(chat_id).await?;
+    *cached = Some(CachedAdmins {
+        fetched_at: Instant::now(),
+        admins: admins.clone(),
+    });
+
+    Ok(admins)
+}
+
+// a user's admin entry in a chat, or None if they aren't an admin there
+pub async fn get_admin(
+    bot: &crate::Bot,
+    chat_id: i64,
+    user_id: i64,
+) -> anyhow::Result<Option<ChatMember>> {
+    let admins = get_admins(bot, chat_id).await?;
+    Ok(admins.into_iter().find(|admin| admin.user.id == user_id))
+}
+
+// forget a chat's admins, they'll be fetched again on the next check
+pub fn invalidate(chat_id: i64) {
+    ADMIN_CACHE.lock().unwrap().remove(&chat_id);
+}
@@ -1,15 +1,86 @@
+pub mod admincache;
 pub mod perms;
 
-use std::{fmt::Display, str::FromStr};
+use std::{fmt::Display, future::Future, str::FromStr, time::Duration};
 
+use chrono::{DateTime, Datelike, NaiveDate, NaiveTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use futures::{stream, StreamExt};
 use sqlx::{Pool, Postgres};
 use teloxide::{
     payloads::SendMessageSetters,
     prelude2::*,
     types::{MessageEntity, MessageEntityKind},
+    utils::html,
+    RequestError,
 };
 
-use crate::repo::users;
+use crate::repo::{connections, timezones, users};
+
+// number of moderation actions allowed in flight at once
+const MAX_CONCURRENT_ACTIONS: usize = 5;
+
+// number of failures listed in a mass action summary
+const MAX_LISTED_FAILURES: usize = 25;
+
+// the user telegram puts as the sender of messages from anonymous admins
+const GROUP_ANONYMOUS_BOT_ID: i64 = 1087968824;
+
+// smallest number taken as a user or channel ID when looking for targets
+const MIN_TARGET_ID: i64 = 10_000;
+
+// whether a message was sent by an admin posting as the group
+pub fn is_anonymous_admin(message: &Message) -> bool {
+    message
+        .sender_chat()
+        .map_or(false, |sender_chat| sender_chat.id == message.chat.id)
+        || message
+            .from()
+            .map_or(false, |user| user.id == GROUP_ANONYMOUS_BOT_ID)
+}
+
+// the ID of whoever sent a message; for messages sent on behalf of a channel
+// that's the channel, not the placeholder user telegram attaches to them
+pub fn sender_id(message: &Message) -> Option<i64> {
+    // anonymous admins send messages as the group itself, there's nobody to act on
+    if is_anonymous_admin(message) {
+        return None;
+    }
+
+    match message.sender_chat() {
+        Some(sender_chat) => Some(sender_chat.id),
+        None => message.from().map(|user| user.id),
+    }
+}
+
+// mention whoever sent a message, channels can't be mentioned so they get their ID
+pub fn sender_mention(message: &Message) -> String {
+    match (message.from(), sender_id(message)) {
+        (Some(user), Some(id)) if id == user.id => {
+            html::user_mention(user.id, &html::escape(&user.full_name()))
+        }
+        (_, Some(id)) => html::code_inline(&id.to_string()),
+        _ => "Someone".to_owned(),
+    }
+}
+
+// the chat a message acts on: where it was sent, or the chat the sender connected their PM to
+pub async fn target_chat(message: &Message, pool: &Pool<Postgres>) -> anyhow::Result<i64> {
+    if !message.chat.is_private() {
+        return Ok(message.chat.id);
+    }
+
+    let connection = match message.from() {
+        Some(user) => connections::get_connection(user.id, pool).await?,
+        None => None,
+    };
+    Ok(connection.unwrap_or(message.chat.id))
+}
+
+// channels have negative IDs, users positive ones
+pub fn is_channel(id: i64) -> bool {
+    id < 0
+}
 
 pub fn id_from_reply(_bot: &crate::Bot, message: &Message) -> (Option<i64>, Option<String>) {
     // check for reply
@@ -18,8 +89,8 @@ pub fn id_from_reply(_bot: &crate::Bot, message: &Message) -> (Option<i64>, Opti
         return (None, None);
     }
 
-    // if can get user from replied-to message
-    if let Some(user) = prev_message.unwrap().from() {
+    // if can get sender from replied-to message
+    if let Some(sender_id) = sender_id(prev_message.unwrap()) {
         // if quoted message has some text
         if let Some(msg_text) = prev_message.unwrap().text() {
             // split into args
@@ -27,11 +98,11 @@ pub fn id_from_reply(_bot: &crate::Bot, message: &Message) -> (Option<i64>, Opti
 
             // no args, return only user ID
             if res.len() < 2 {
-                return (Some(user.id), Some("".to_owned()));
+                return (Some(sender_id), Some("".to_owned()));
             }
 
             // return user ID and text
-            return (Some(user.id), Some(res[1].to_owned()));
+            return (Some(sender_id), Some(res[1].to_owned()));
         }
     }
 
@@ -88,8 +159,8 @@ pub async fn extract_user_and_text(
                 let user_name = args[0];
                 let res =
                     users::get_user(None, Some(user_name.to_string().replace('@', "")), pool).await;
-                if res.is_ok() {
-                    user_id = Some(res.unwrap().user_id);
+                if let Ok(user) = res {
+                    user_id = Some(user.user_id);
                     let split: Vec<_> = msg_text.splitn(3, char::is_whitespace).collect();
                     if split.len() >= 3 {
                         text = Some(split[2].to_owned());
@@ -145,86 +216,416 @@ pub async fn extract_user_and_text(
     (None, None)
 }
 
-pub enum UnitOfTime {
-    Seconds(u64),
-    Minutes(u64),
-    Hours(u64),
-    Days(u64),
+// convert a UTF-16 offset (as used by telegram entities) into a byte offset
+pub fn byte_offset(text: &str, utf16_offset: usize) -> usize {
+    let mut utf16_len = 0;
+    for (idx, c) in text.char_indices() {
+        if utf16_len >= utf16_offset {
+            return idx;
+        }
+        utf16_len += c.len_utf16();
+    }
+    text.len()
+}
+
+// resolve a single target that isn't a text mention: a @username or a user ID
+async fn resolve_target(token: &str, pool: &Pool<Postgres>) -> Option<Result<i64, String>> {
+    if let Some(user_name) = token.strip_prefix('@') {
+        return Some(
+            users::get_user(None, Some(user_name.to_lowercase()), pool)
+                .await
+                .map(|user| user.user_id)
+                .map_err(|_| {
+                    format!(
+                        "{}: Could not find a user by this name; are you sure I've seen them before?",
+                        html::escape(token)
+                    )
+                }),
+        );
+    }
+
+    // short numbers are more likely a count or a duration than someone's ID
+    token
+        .parse::<i64>()
+        .ok()
+        .filter(|id| id.abs() >= MIN_TARGET_ID)
+        .map(Ok)
+}
+
+// like extract_user_and_text, but collects every leading id/username/text mention;
+// targets that couldn't be resolved are returned as errors
+pub async fn extract_users_and_text(
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> (Vec<Result<i64, String>>, Option<String>) {
+    let msg_text = match message.text() {
+        Some(text) => text,
+        None => return (vec![], None),
+    };
+    let entities = message.entities().unwrap_or_default();
+
+    // skip the command itself
+    let mut pos = msg_text.find(char::is_whitespace).unwrap_or(msg_text.len());
+    let mut targets = vec![];
+
+    loop {
+        // skip whitespace between targets
+        pos += msg_text[pos..].len() - msg_text[pos..].trim_start().len();
+        if pos >= msg_text.len() {
+            break;
+        }
+
+        // text mentions can span multiple words
+        let utf16_pos: usize = msg_text[..pos].encode_utf16().count();
+        let mention = entities.iter().find_map(|entity| match &entity.kind {
+            MessageEntityKind::TextMention { user } if entity.offset == utf16_pos => Some((
+                user.id,
+                byte_offset(msg_text, entity.offset + entity.length),
+            )),
+            _ => None,
+        });
+        if let Some((user_id, end)) = mention {
+            targets.push(Ok(user_id));
+            pos = end;
+            continue;
+        }
+
+        let end = msg_text[pos..]
+            .find(char::is_whitespace)
+            .map(|idx| pos + idx)
+            .unwrap_or(msg_text.len());
+        match resolve_target(&msg_text[pos..end], pool).await {
+            Some(target) => {
+                targets.push(target);
+                pos = end;
+            }
+            // the rest is regular text
+            None => break,
+        }
+    }
+
+    let text = Some(msg_text[pos..].trim().to_owned()).filter(|text| !text.is_empty());
+
+    // fall back to the author of the replied-to message
+    if targets.is_empty() {
+        if let Some(sender_id) = message.reply_to_message().and_then(sender_id) {
+            targets.push(Ok(sender_id));
+        }
+    }
+
+    (targets, text)
+}
+
+// send a request, backing off and trying again for as long as we're being rate limited
+pub async fn retry_rate_limited<F, Fut>(request: F) -> anyhow::Result<()>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = anyhow::Result<()>>,
+{
+    loop {
+        match request().await {
+            Err(err) => match err.downcast_ref::<RequestError>() {
+                Some(RequestError::RetryAfter(secs)) => {
+                    tokio::time::sleep(Duration::from_secs(*secs as u64)).await;
+                }
+                _ => return Err(err),
+            },
+            res => return res,
+        }
+    }
+}
+
+// run an action against several users concurrently, and summarize how it went
+// along with who it worked on
+pub async fn mass_action<F, Fut>(
+    targets: Vec<Result<i64, String>>,
+    verb: &str,
+    action: F,
+) -> (String, Vec<i64>)
+where
+    F: Fn(i64) -> Fut,
+    Fut: Future<Output = Result<(), String>>,
+{
+    let total = targets.len();
+    let mut failures = vec![];
+    let mut user_ids = vec![];
+    for target in targets {
+        match target {
+            Ok(user_id) => user_ids.push(user_id),
+            Err(reason) => failures.push(reason),
+        }
+    }
+
+    let results: Vec<_> = stream::iter(user_ids)
+        .map(|user_id| {
+            let fut = action(user_id);
+            async move { (user_id, fut.await) }
+        })
+        .buffer_unordered(MAX_CONCURRENT_ACTIONS)
+        .collect()
+        .await;
+
+    let mut succeeded = vec![];
+    for (user_id, res) in results {
+        match res {
+            Ok(_) => succeeded.push(user_id),
+            Err(reason) => failures.push(format!(
+                "{}: {}",
+                html::code_inline(&user_id.to_string()),
+                reason
+            )),
+        }
+    }
+
+    let mut summary = format!("{} {} of {} user(s).", verb, total - failures.len(), total);
+    if !failures.is_empty() {
+        summary.push_str("\nFailed:");
+        for failure in failures.iter().take(MAX_LISTED_FAILURES) {
+            summary.push_str(&format!("\n- {}", failure));
+        }
+        // keep the summary within telegram's message length limit
+        if failures.len() > MAX_LISTED_FAILURES {
+            summary.push_str(&format!(
+                "\n...and {} more",
+                failures.len() - MAX_LISTED_FAILURES
+            ));
+        }
+    }
+
+    (summary, succeeded)
+}
+
+// telegram treats restrictions shorter than 30 seconds or longer than 366 days as permanent
+const MIN_DURATION: i64 = 30;
+const MAX_DURATION: i64 = 366 * 24 * 3600;
+
+// allowed units and their length in seconds
+const UNITS: &[(&[&str], i64)] = &[
+    (&["s", "sec", "secs", "second", "seconds"], 1),
+    (&["m", "min", "mins", "minute", "minutes"], 60),
+    (&["h", "hr", "hrs", "hour", "hours"], 3600),
+    (&["d", "day", "days"], 24 * 3600),
+    (&["w", "wk", "wks", "week", "weeks"], 7 * 24 * 3600),
+    (&["mo", "mon", "month", "months"], 30 * 24 * 3600),
+];
+
+const DURATION_HELP: &str =
+    "Allowed units: s, m, h, d, w, mo (seconds, minutes, hours, days, weeks, months), eg: 90min, 2w or 1d12h";
+
+// a duration for timed actions, eg: '1d12h', '2 weeks' or '90min'
+#[derive(Clone, Copy)]
+pub struct TimeSpan(i64);
+
+impl TimeSpan {
+    pub fn from_seconds(seconds: i64) -> Self {
+        TimeSpan(seconds)
+    }
+
+    pub fn seconds(&self) -> i64 {
+        self.0
+    }
+
+    // the point in time this span ends at, counting from the given time
+    pub fn until(&self, from: DateTime<Utc>) -> DateTime<Utc> {
+        from + chrono::Duration::seconds(self.0)
+    }
 }
 
-impl FromStr for UnitOfTime {
+impl FromStr for TimeSpan {
     type Err = &'static str;
     fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
-        // attempt to split into two
-        let split_s: Vec<_> = s.splitn(2, char::is_whitespace).collect();
-        let parsed_num; // parsed number
-        let u; // unit of time
-
-        // user specified as a single slug, eg: '1m'
-        if split_s.len() == 1
-            && split_s[0].ends_with(&['h', 'm', 's', 'd'][..]) // check if suffixes are valid
-            && split_s[0].len() >= 2
-        // check if it's not _only_ a suffix
-        {
-            let mut time = split_s[0].to_owned(); // apparently only Strings can be popped
-            let unit = time.pop().unwrap().to_string(); // pop suffix from string
-            time = time.to_string(); // remaining part is time
-
-            // attempt to parse time string
-            parsed_num = match time.parse::<u64>() {
-                Ok(n) => n,
-                Err(_) => {
-                    return Err("Allowed units: h, m, s, d");
-                }
-            };
-            u = unit;
-        // user specified with a whitespace, eg: '1 m'
-        } else if split_s.len() == 2 {
-            // attempt to parse time string
-            parsed_num = match split_s[0].parse::<u64>() {
-                Ok(n) => n,
-                Err(_) => {
-                    return Err("Allowed units: h, m, s, d");
-                }
-            };
+        // whitespace is only for readability, eg: '1 d 12 h'
+        let s: String = s
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect::<String>()
+            .to_lowercase();
+        if s.is_empty() {
+            return Err(DURATION_HELP);
+        }
 
-            // second half is the unit
-            u = split_s[1].to_owned()
-        } else {
-            // user is a dumbass
-            return Err("Allowed units: h, m, s, d");
+        let mut total: i64 = 0;
+        let mut rest = s.as_str();
+        while !rest.is_empty() {
+            // every part is a number followed by a unit
+            let num_end = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .ok_or(DURATION_HELP)?;
+            let unit_end = rest[num_end..]
+                .find(|c: char| c.is_ascii_digit())
+                .map(|idx| num_end + idx)
+                .unwrap_or(rest.len());
+
+            let num = rest[..num_end].parse::<i64>().map_err(|_| DURATION_HELP)?;
+            let unit = &rest[num_end..unit_end];
+            let secs = UNITS
+                .iter()
+                .find(|(names, _)| names.contains(&unit))
+                .map(|(_, secs)| *secs)
+                .ok_or(DURATION_HELP)?;
+
+            total = num
+                .checked_mul(secs)
+                .and_then(|secs| total.checked_add(secs))
+                .ok_or("Durations can't be longer than 366 days.")?;
+            rest = &rest[unit_end..];
         }
 
-        // attempt to match suffixes to units of time
-        match &u as &str {
-            "h" | "hours" => Ok(UnitOfTime::Hours(parsed_num)),
-            "m" | "minutes" => Ok(UnitOfTime::Minutes(parsed_num)),
-            "s" | "seconds" => Ok(UnitOfTime::Seconds(parsed_num)),
-            "d" | "days" => Ok(UnitOfTime::Days(parsed_num)),
-            _ => Err("Allowed units: h, m, s, d"), // user is a dumbass
+        if total < MIN_DURATION {
+            return Err("Durations need to be at least 30 seconds.");
         }
+        if total > MAX_DURATION {
+            return Err("Durations can't be longer than 366 days.");
+        }
+
+        Ok(TimeSpan(total))
     }
 }
 
-// useful for formatting while sending
-impl Display for UnitOfTime {
+// useful for formatting while sending, eg: '1 day(s), 12 hour(s)'
+impl Display for TimeSpan {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            UnitOfTime::Seconds(t) => write!(f, "{} second(s)", t),
-            UnitOfTime::Minutes(t) => write!(f, "{} minute(s)", t),
-            UnitOfTime::Hours(t) => write!(f, "{} hour(s)", t),
-            UnitOfTime::Days(t) => write!(f, "{} day(s)", t),
+        let parts = [
+            (7 * 24 * 3600, "week(s)"),
+            (24 * 3600, "day(s)"),
+            (3600, "hour(s)"),
+            (60, "minute(s)"),
+            (1, "second(s)"),
+        ];
+
+        let mut rest = self.0;
+        let mut out = vec![];
+        for (secs, name) in parts {
+            if rest >= secs {
+                out.push(format!("{} {}", rest / secs, name));
+                rest %= secs;
+            }
         }
+
+        write!(f, "{}", out.join(", "))
     }
 }
 
-// get time in seconds from unit and number of units
-pub fn extract_time(unit: &UnitOfTime) -> u64 {
-    match unit {
-        UnitOfTime::Hours(t) => t * 3600,
-        UnitOfTime::Minutes(t) => t * 60,
-        UnitOfTime::Seconds(t) => *t,
-        UnitOfTime::Days(t) => t * 3600 * 24,
+// the chat's configured timezone, or UTC if it never set one
+pub async fn chat_timezone(chat_id: i64, pool: &Pool<Postgres>) -> anyhow::Result<Tz> {
+    Ok(timezones::get_timezone(chat_id, pool)
+        .await?
+        .and_then(|tz| tz.parse::<Tz>().ok())
+        .unwrap_or(Tz::UTC))
+}
+
+const END_TIME_HELP: &str = "Expected a time like <code>until 2026-11-01 18:00</code>, <code>until friday 9am</code> or <code>until 18:00</code>";
+
+// parse a time of day, eg: '18:00', '9am' or '9:30pm'
+fn parse_time_of_day(s: &str) -> Option<NaiveTime> {
+    let (s, offset) = if let Some(s) = s.strip_suffix("am") {
+        (s, Some(0))
+    } else if let Some(s) = s.strip_suffix("pm") {
+        (s, Some(12))
+    } else {
+        (s, None)
+    };
+
+    let (hour, minute) = match s.split_once(':') {
+        Some((hour, minute)) => (hour.parse::<u32>().ok()?, minute.parse::<u32>().ok()?),
+        None => (s.parse::<u32>().ok()?, 0),
+    };
+    let hour = match offset {
+        Some(offset) if (1..=12).contains(&hour) => hour % 12 + offset,
+        Some(_) => return None,
+        None => hour,
+    };
+
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+// parse a point in time in the given timezone, eg: '2026-11-01 18:00', 'friday 9am' or '18:00'
+fn parse_time_point(s: &str, now: DateTime<Tz>) -> Option<DateTime<Utc>> {
+    let s = s.to_lowercase();
+    let words: Vec<_> = s.split_whitespace().collect();
+    let today = now.date().naive_local();
+    let midnight = NaiveTime::from_hms(0, 0, 0);
+
+    // the date, time, and how many days to skip ahead if that's already passed
+    let (date, time, skip) = match words[..] {
+        [date] if NaiveDate::parse_from_str(date, "%Y-%m-%d").is_ok() => (
+            NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?,
+            midnight,
+            0,
+        ),
+        [date, time] if NaiveDate::parse_from_str(date, "%Y-%m-%d").is_ok() => (
+            NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?,
+            parse_time_of_day(time)?,
+            0,
+        ),
+        ["today", time] => (today, parse_time_of_day(time)?, 0),
+        ["tomorrow"] => (today.succ(), midnight, 0),
+        ["tomorrow", time] => (today.succ(), parse_time_of_day(time)?, 0),
+        [day] | [day, _] if day.parse::<Weekday>().is_ok() => {
+            let weekday = day.parse::<Weekday>().ok()?;
+            let days_ahead =
+                (weekday.num_days_from_monday() + 7 - today.weekday().num_days_from_monday()) % 7;
+            let time = match words.get(1) {
+                Some(time) => parse_time_of_day(time)?,
+                None => midnight,
+            };
+            (today + chrono::Duration::days(days_ahead as i64), time, 7)
+        }
+        [time] => (today, parse_time_of_day(time)?, 1),
+        _ => return None,
+    };
+
+    let mut end = now
+        .timezone()
+        .from_local_datetime(&date.and_time(time))
+        .earliest()?;
+    if end <= now {
+        end = end + chrono::Duration::days(skip);
+    }
+
+    Some(end.with_timezone(&Utc))
+}
+
+// parse when a timed action should end, either after a duration or 'until' a point in time,
+// returning the end time and a description of it in the chat's timezone
+pub fn parse_end_time(
+    s: &str,
+    from: DateTime<Utc>,
+    tz: Tz,
+) -> Result<(DateTime<Utc>, String), &'static str> {
+    let s = s.trim();
+    let until = s
+        .split_once(char::is_whitespace)
+        .filter(|(first, _)| first.eq_ignore_ascii_case("until"))
+        .map(|(_, rest)| rest);
+
+    match until {
+        Some(until) => {
+            let end = parse_time_point(until, from.with_timezone(&tz)).ok_or(END_TIME_HELP)?;
+            let secs = end.signed_duration_since(from).num_seconds();
+            if secs <= 0 {
+                return Err("That time has already passed!");
+            }
+            if secs < MIN_DURATION {
+                return Err("That's less than 30 seconds away; pick a later time.");
+            }
+            if secs > MAX_DURATION {
+                return Err("That's more than 366 days away; pick an earlier time.");
+            }
+
+            Ok((
+                end,
+                format!(
+                    "until {} ({})",
+                    end.with_timezone(&tz).format("%Y-%m-%d %H:%M"),
+                    tz.name()
+                ),
+            ))
+        }
+        None => {
+            let span = s.parse::<TimeSpan>()?;
+            Ok((span.until(from), format!("for {}", span)))
+        }
     }
 }
 
@@ -260,3 +661,243 @@ impl FromStr for PinMode {
         })
     }
 }
+
+// what automated moderation does to users who trip a filter
+#[derive(Clone, Copy, PartialEq)]
+pub enum FilterAction {
+    Ban,
+    Kick,
+    Mute,
+}
+
+impl FromStr for FilterAction {
+    type Err = &'static str;
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        match s {
+            "ban" => Ok(FilterAction::Ban),
+            "kick" => Ok(FilterAction::Kick),
+            "mute" => Ok(FilterAction::Mute),
+            _ => Err("Allowed actions: ban, kick, mute"),
+        }
+    }
+}
+
+impl FilterAction {
+    pub fn past_tense(&self) -> &'static str {
+        match self {
+            FilterAction::Ban => "banned",
+            FilterAction::Kick => "kicked",
+            FilterAction::Mute => "muted",
+        }
+    }
+}
+
+impl Display for FilterAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FilterAction::Ban => write!(f, "ban"),
+            FilterAction::Kick => write!(f, "kick"),
+            FilterAction::Mute => write!(f, "mute"),
+        }
+    }
+}
+
+// bot-level roles that let trusted users moderate without being telegram admins
+#[derive(Clone, Copy, PartialEq)]
+pub enum Role {
+    Helper,
+    Moderator,
+}
+
+// commands helpers are allowed to use
+const HELPER_COMMANDS: [&str; 3] = ["mute", "tmute", "unmute"];
+
+// commands moderators are allowed to use, on top of the helper ones
+const MODERATOR_COMMANDS: [&str; 9] = [
+    "ban",
+    "tban",
+    "kick",
+    "unban",
+    "restrict",
+    "unrestrict",
+    "approve",
+    "unapprove",
+    "approved",
+];
+
+impl FromStr for Role {
+    type Err = &'static str;
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        match s {
+            "helper" => Ok(Role::Helper),
+            "moderator" | "mod" => Ok(Role::Moderator),
+            _ => Err("Allowed roles: helper, moderator"),
+        }
+    }
+}
+
+impl Role {
+    // check if the role grants access to a command, given without its leading slash
+    pub fn allows(&self, command: &str) -> bool {
+        match self {
+            Role::Helper => HELPER_COMMANDS.contains(&command),
+            Role::Moderator => {
+                HELPER_COMMANDS.contains(&command) || MODERATOR_COMMANDS.contains(&command)
+            }
+        }
+    }
+
+    pub fn commands(&self) -> Vec<&'static str> {
+        match self {
+            Role::Helper => HELPER_COMMANDS.to_vec(),
+            Role::Moderator => [&HELPER_COMMANDS[..], &MODERATOR_COMMANDS[..]].concat(),
+        }
+    }
+}
+
+impl Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Role::Helper => write!(f, "helper"),
+            Role::Moderator => write!(f, "moderator"),
+        }
+    }
+}
+
+// the command a message invokes, lowercased and without the slash or the bot's username
+pub fn message_command(message: &Message) -> Option<String> {
+    let command = message
+        .text()?
+        .split_whitespace()
+        .next()?
+        .strip_prefix('/')?;
+    let command = command.split('@').next().unwrap_or_default();
+    Some(command.to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(s: &str) -> Result<i64, &'static str> {
+        s.parse::<TimeSpan>().map(|span| span.seconds())
+    }
+
+    #[test]
+    fn time_span_units() {
+        assert_eq!(span("90s"), Ok(90));
+        assert_eq!(span("90min"), Ok(90 * 60));
+        assert_eq!(span("2 weeks"), Ok(14 * 24 * 3600));
+        assert_eq!(span("1mo"), Ok(30 * 24 * 3600));
+        assert_eq!(span("1D 12H"), Ok(36 * 3600));
+        assert_eq!(span("1h30m15s"), Ok(5415));
+    }
+
+    #[test]
+    fn time_span_invalid() {
+        assert!(span("").is_err());
+        assert!(span("5").is_err());
+        assert!(span("h").is_err());
+        assert!(span("5y").is_err());
+        assert!(span("1h-5m").is_err());
+    }
+
+    #[test]
+    fn time_span_bounds() {
+        assert!(span("29s").is_err());
+        assert_eq!(span("30s"), Ok(30));
+        assert_eq!(span("366d"), Ok(366 * 24 * 3600));
+        assert!(span("366d1s").is_err());
+    }
+
+    #[test]
+    fn time_span_overflow() {
+        assert!(span("99999999999999999999s").is_err());
+        assert!(span("9223372036854775807w").is_err());
+        assert!(span("9000000000000000000s9000000000000000000s").is_err());
+    }
+
+    fn end(s: &str, tz: Tz) -> Result<(String, String), &'static str> {
+        let from = Utc.ymd(2026, 10, 19).and_hms(12, 0, 0); // a monday
+        parse_end_time(s, from, tz)
+            .map(|(end, desc)| (end.format("%Y-%m-%d %H:%M").to_string(), desc))
+    }
+
+    #[test]
+    fn end_time_durations() {
+        assert_eq!(
+            end("1d12h", Tz::UTC),
+            Ok((
+                "2026-10-21 00:00".to_owned(),
+                "for 1 day(s), 12 hour(s)".to_owned()
+            ))
+        );
+        assert!(end("10s", Tz::UTC).is_err());
+    }
+
+    #[test]
+    fn end_time_points() {
+        assert_eq!(end("until 18:00", Tz::UTC).unwrap().0, "2026-10-19 18:00");
+        assert_eq!(
+            end("until tomorrow", Tz::UTC).unwrap().0,
+            "2026-10-20 00:00"
+        );
+        assert_eq!(
+            end("until friday 9am", Tz::UTC).unwrap().0,
+            "2026-10-23 09:00"
+        );
+        assert_eq!(
+            end("UNTIL 2026-11-01 18:00", Tz::UTC).unwrap().0,
+            "2026-11-01 18:00"
+        );
+        assert!(end("until 13pm", Tz::UTC).is_err());
+        assert!(end("until someday", Tz::UTC).is_err());
+    }
+
+    #[test]
+    fn end_time_wraps_past_times() {
+        // times of day that already passed mean tomorrow, weekdays mean next week
+        assert_eq!(end("until 9am", Tz::UTC).unwrap().0, "2026-10-20 09:00");
+        assert_eq!(end("until 12:00", Tz::UTC).unwrap().0, "2026-10-20 12:00");
+        assert_eq!(
+            end("until monday 9am", Tz::UTC).unwrap().0,
+            "2026-10-26 09:00"
+        );
+        assert_eq!(
+            end("until today 9am", Tz::UTC),
+            Err("That time has already passed!")
+        );
+    }
+
+    #[test]
+    fn end_time_bounds() {
+        let from = Utc.ymd(2026, 10, 19).and_hms(11, 59, 45);
+        assert_eq!(
+            parse_end_time("until 2026-10-19 12:00", from, Tz::UTC).map(|(_, desc)| desc),
+            Err("That's less than 30 seconds away; pick a later time.")
+        );
+        assert_eq!(
+            end("until 2026-10-19 12:00", Tz::UTC),
+            Err("That time has already passed!")
+        );
+        assert!(end("until 2027-10-20", Tz::UTC).is_ok());
+        assert_eq!(
+            end("until 2027-10-21", Tz::UTC),
+            Err("That's more than 366 days away; pick an earlier time.")
+        );
+    }
+
+    #[test]
+    fn end_time_timezones() {
+        let berlin: Tz = "Europe/Berlin".parse().unwrap();
+        assert_eq!(
+            end("until 18:00", berlin),
+            Ok((
+                "2026-10-19 16:00".to_owned(),
+                "until 2026-10-19 18:00 (Europe/Berlin)".to_owned()
+            ))
+        );
+        // clocks skip from 2:00 to 3:00 that night
+        assert!(end("until 2027-03-28 02:30", berlin).is_err());
+    }
+}
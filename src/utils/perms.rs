@@ -1,19 +1,48 @@
-use crate::BOT_ID;
+use crate::{
+    repo::{approvals, roles},
+    utils::{self, admincache, Role},
+    BOT_ID, POOL,
+};
 use anyhow::anyhow;
+use sqlx::{Pool, Postgres};
 use teloxide::{
     payloads::SendMessageSetters,
     prelude2::*,
     types::{ChatMember, ChatMemberKind, ChatMemberStatus},
 };
 
-async fn is_bot_admin(bot: &crate::Bot, message: &Message) -> anyhow::Result<()> {
-    if message.chat.is_private() {
-        return Ok(());
+// PMs that aren't connected to a group have no permissions to check
+async fn checked_chat(message: &Message) -> anyhow::Result<Option<i64>> {
+    let chat_id = utils::target_chat(message, &POOL).await?;
+    if chat_id != message.chat.id || !message.chat.is_private() {
+        Ok(Some(chat_id))
+    } else {
+        Ok(None)
+    }
+}
+
+// a user's admin entry in the chat a message acts on, nobody administers an unconnected PM
+async fn get_target_admin(
+    bot: &crate::Bot,
+    message: &Message,
+    user_id: i64,
+) -> anyhow::Result<Option<ChatMember>> {
+    match checked_chat(message).await? {
+        Some(chat_id) => admincache::get_admin(bot, chat_id, user_id).await,
+        None => Ok(None),
     }
+}
 
-    let bot_member: ChatMember = bot.get_chat_member(message.chat.id, *BOT_ID).await?;
+async fn is_bot_admin(bot: &crate::Bot, message: &Message) -> anyhow::Result<()> {
+    let chat_id = match checked_chat(message).await? {
+        Some(chat_id) => chat_id,
+        None => return Ok(()),
+    };
 
-    if let ChatMemberStatus::Administrator = bot_member.status() {
+    if admincache::get_admin(bot, chat_id, *BOT_ID)
+        .await?
+        .is_some()
+    {
         return Ok(());
     }
 
@@ -40,17 +69,32 @@ pub async fn is_user_admin(
     message: &Message,
     user_id: i64,
 ) -> anyhow::Result<()> {
-    if message.chat.is_private() {
-        return Ok(());
+    let chat_id = match checked_chat(message).await? {
+        Some(chat_id) => chat_id,
+        None => return Ok(()),
+    };
+
+    match admincache::get_admin(bot, chat_id, user_id).await? {
+        Some(_) => Ok(()),
+        None => Err(anyhow!("User is not admin")),
     }
+}
 
-    let chat_member: ChatMember = bot.get_chat_member(message.chat.id, user_id).await?;
+// trusted users can run some commands through their bot role instead of telegram rights
+async fn has_role_for_command(
+    message: &Message,
+    chat_id: i64,
+    user_id: i64,
+) -> anyhow::Result<bool> {
+    let command = match utils::message_command(message) {
+        Some(command) => command,
+        None => return Ok(false),
+    };
 
-    match chat_member.status() {
-        ChatMemberStatus::Administrator => Ok(()),
-        ChatMemberStatus::Owner => Ok(()),
-        _ => Err(anyhow!("User is not admin")),
-    }
+    let role = roles::get_role(chat_id, user_id, &POOL).await?;
+    Ok(role
+        .and_then(|role| role.parse::<Role>().ok())
+        .map_or(false, |role| role.allows(&command)))
 }
 
 pub async fn require_user_admin(bot: &crate::Bot, message: &Message) -> anyhow::Result<()> {
@@ -61,15 +105,54 @@ pub async fn require_user_admin(bot: &crate::Bot, message: &Message) -> anyhow::
         }
     };
 
-    match is_user_admin(bot, message, user_id).await {
-        Ok(_) => Ok(()),
-        Err(_) => {
-            bot.send_message(message.chat.id, "You need to be an admin for this to work!")
-                .reply_to_message_id(message.id)
-                .await?;
-            Err(anyhow!("User is not admin"))
+    let chat_id = utils::target_chat(message, &POOL).await?;
+    if is_user_admin(bot, message, user_id).await.is_ok()
+        || has_role_for_command(message, chat_id, user_id).await?
+    {
+        return Ok(());
+    }
+
+    bot.send_message(message.chat.id, "You need to be an admin for this to work!")
+        .reply_to_message_id(message.id)
+        .await?;
+    Err(anyhow!("User is not admin"))
+}
+
+pub async fn require_chat_owner(bot: &crate::Bot, message: &Message) -> anyhow::Result<()> {
+    if let Some(user) = message.from() {
+        let chat_member = get_target_admin(bot, message, user.id).await?;
+
+        if let Some(ChatMemberStatus::Owner) = chat_member.map(|member| member.status()) {
+            return Ok(());
         }
     }
+
+    bot.send_message(message.chat.id, "Only the chat owner can do this!")
+        .reply_to_message_id(message.id)
+        .await?;
+    Err(anyhow!("User is not the chat owner"))
+}
+
+pub async fn is_user_approved(
+    chat_id: i64,
+    user_id: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<bool> {
+    approvals::is_approved(chat_id, user_id, pool).await
+}
+
+// admins and approved users are exempt from automated moderation
+pub async fn is_user_exempt(
+    bot: &crate::Bot,
+    chat_id: i64,
+    user_id: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<bool> {
+    if is_user_approved(chat_id, user_id, pool).await? {
+        return Ok(true);
+    }
+
+    is_chat_admin(bot, chat_id, user_id).await
 }
 
 pub async fn is_user_restricted(
@@ -77,11 +160,12 @@ pub async fn is_user_restricted(
     message: &Message,
     user_id: i64,
 ) -> anyhow::Result<bool> {
-    if message.chat.is_private() {
-        return Ok(false);
-    }
+    let chat_id = match checked_chat(message).await? {
+        Some(chat_id) => chat_id,
+        None => return Ok(false),
+    };
 
-    let chat_member = bot.get_chat_member(message.chat.id, user_id).await?;
+    let chat_member = bot.get_chat_member(chat_id, user_id).await?;
 
     let is_restricted = chat_member.kind.can_send_messages()
         && chat_member.kind.can_send_media_messages()
@@ -94,9 +178,9 @@ pub async fn require_bot_restrict_chat_members(
     bot: &crate::Bot,
     message: &Message,
 ) -> anyhow::Result<()> {
-    let chat_member = bot.get_chat_member(message.chat.id, *BOT_ID).await?;
+    let chat_member = get_target_admin(bot, message, *BOT_ID).await?;
 
-    if let ChatMemberKind::Administrator(adm) = &chat_member.kind {
+    if let Some(ChatMemberKind::Administrator(adm)) = chat_member.map(|member| member.kind) {
         if adm.can_restrict_members {
             return Ok(());
         }
@@ -118,19 +202,22 @@ pub async fn require_restrict_chat_members(
     let user = message.from();
 
     if let Some(user) = user {
-        let chat_member: ChatMember = bot.get_chat_member(message.chat.id, user.id).await?;
+        let chat_id = utils::target_chat(message, &POOL).await?;
+        let chat_member = get_target_admin(bot, message, user.id).await?;
 
-        match &chat_member.kind {
-            ChatMemberKind::Owner(_) => {
+        match chat_member.map(|member| member.kind) {
+            Some(ChatMemberKind::Owner(_)) => {
                 return Ok(());
             }
-            ChatMemberKind::Administrator(adm) => {
-                if adm.can_restrict_members {
-                    return Ok(());
-                }
+            Some(ChatMemberKind::Administrator(adm)) if adm.can_restrict_members => {
+                return Ok(());
             }
             _ => {}
         }
+
+        if has_role_for_command(message, chat_id, user.id).await? {
+            return Ok(());
+        }
     }
 
     bot.send_message(
@@ -154,13 +241,35 @@ pub async fn require_group(bot: &crate::Bot, message: &Message) -> anyhow::Resul
     Err(anyhow!("This command is meant to be used in a group"))
 }
 
+// like require_group, but also accepts PMs connected to a group
+pub async fn require_group_or_connection(
+    bot: &crate::Bot,
+    message: &Message,
+) -> anyhow::Result<()> {
+    let chat = &message.chat;
+    if chat.is_group() || chat.is_supergroup() {
+        return Ok(());
+    }
+    if chat.is_private() && utils::target_chat(message, &POOL).await? != chat.id {
+        return Ok(());
+    }
+
+    bot.send_message(
+        chat.id,
+        "This command is meant to be used in a group, or here after connecting to one with /connect!",
+    )
+    .reply_to_message_id(message.id)
+    .await?;
+    Err(anyhow!("This command is meant to be used in a group"))
+}
+
 pub async fn require_bot_promote_chat_members(
     bot: &crate::Bot,
     message: &Message,
 ) -> anyhow::Result<()> {
-    let chat_member = bot.get_chat_member(message.chat.id, *BOT_ID).await?;
+    let chat_member = get_target_admin(bot, message, *BOT_ID).await?;
 
-    if let ChatMemberKind::Administrator(adm) = &chat_member.kind {
+    if let Some(ChatMemberKind::Administrator(adm)) = chat_member.map(|member| member.kind) {
         if adm.can_promote_members {
             return Ok(());
         }
@@ -182,16 +291,14 @@ pub async fn require_promote_chat_members(
     let user = message.from();
 
     if let Some(user) = user {
-        let chat_member: ChatMember = bot.get_chat_member(message.chat.id, user.id).await?;
+        let chat_member = get_target_admin(bot, message, user.id).await?;
 
-        match &chat_member.kind {
-            ChatMemberKind::Owner(_) => {
+        match chat_member.map(|member| member.kind) {
+            Some(ChatMemberKind::Owner(_)) => {
                 return Ok(());
             }
-            ChatMemberKind::Administrator(adm) => {
-                if adm.can_promote_members {
-                    return Ok(());
-                }
+            Some(ChatMemberKind::Administrator(adm)) if adm.can_promote_members => {
+                return Ok(());
             }
             _ => {}
         }
@@ -210,16 +317,14 @@ pub async fn require_can_pin_messages(bot: &crate::Bot, message: &Message) -> an
     let user = message.from();
 
     if let Some(user) = user {
-        let chat_member: ChatMember = bot.get_chat_member(message.chat.id, user.id).await?;
+        let chat_member = get_target_admin(bot, message, user.id).await?;
 
-        match &chat_member.kind {
-            ChatMemberKind::Owner(_) => {
+        match chat_member.map(|member| member.kind) {
+            Some(ChatMemberKind::Owner(_)) => {
                 return Ok(());
             }
-            ChatMemberKind::Administrator(adm) => {
-                if adm.can_pin_messages.unwrap_or(false) {
-                    return Ok(());
-                }
+            Some(ChatMemberKind::Administrator(adm)) if adm.can_pin_messages.unwrap_or(false) => {
+                return Ok(());
             }
             _ => {}
         }
@@ -238,9 +343,9 @@ pub async fn require_bot_can_pin_messages(
     bot: &crate::Bot,
     message: &Message,
 ) -> anyhow::Result<()> {
-    let chat_member = bot.get_chat_member(message.chat.id, *BOT_ID).await?;
+    let chat_member = get_target_admin(bot, message, *BOT_ID).await?;
 
-    if let ChatMemberKind::Administrator(adm) = &chat_member.kind {
+    if let Some(ChatMemberKind::Administrator(adm)) = chat_member.map(|member| member.kind) {
         if adm.can_pin_messages.unwrap_or(false) {
             return Ok(());
         }
@@ -254,3 +359,123 @@ pub async fn require_bot_can_pin_messages(
     .await?;
     Err(anyhow!("Bot cannot pin messages"))
 }
+
+pub async fn require_can_delete_messages(
+    bot: &crate::Bot,
+    message: &Message,
+) -> anyhow::Result<()> {
+    let user = message.from();
+
+    if let Some(user) = user {
+        let chat_member = get_target_admin(bot, message, user.id).await?;
+
+        match chat_member.map(|member| member.kind) {
+            Some(ChatMemberKind::Owner(_)) => {
+                return Ok(());
+            }
+            Some(ChatMemberKind::Administrator(adm)) if adm.can_delete_messages => {
+                return Ok(());
+            }
+            _ => {}
+        }
+    }
+
+    bot.send_message(
+        message.chat.id,
+        "You're missing the required permission for this action: CAN_DELETE_MESSAGES.",
+    )
+    .reply_to_message_id(message.id)
+    .await?;
+    Err(anyhow!("User cannot delete messages"))
+}
+
+pub async fn require_bot_can_delete_messages(
+    bot: &crate::Bot,
+    message: &Message,
+) -> anyhow::Result<()> {
+    let chat_member = get_target_admin(bot, message, *BOT_ID).await?;
+
+    if let Some(ChatMemberKind::Administrator(adm)) = chat_member.map(|member| member.kind) {
+        if adm.can_delete_messages {
+            return Ok(());
+        }
+    }
+
+    bot.send_message(
+        message.chat.id,
+        "I am missing the required permission for this action: CAN_DELETE_MESSAGES.",
+    )
+    .reply_to_message_id(message.id)
+    .await?;
+    Err(anyhow!("Bot cannot delete messages"))
+}
+
+pub async fn can_user_restrict_members(
+    bot: &crate::Bot,
+    chat_id: i64,
+    user_id: i64,
+) -> anyhow::Result<bool> {
+    let chat_member = admincache::get_admin(bot, chat_id, user_id).await?;
+
+    Ok(match chat_member.map(|member| member.kind) {
+        Some(ChatMemberKind::Owner(_)) => true,
+        Some(ChatMemberKind::Administrator(adm)) => adm.can_restrict_members,
+        _ => false,
+    })
+}
+
+// whether a user may run a restricting command in a chat other than the one it was sent in
+pub async fn can_restrict_in_chat(
+    bot: &crate::Bot,
+    message: &Message,
+    chat_id: i64,
+    user_id: i64,
+) -> anyhow::Result<bool> {
+    Ok(can_user_restrict_members(bot, chat_id, user_id).await?
+        || has_role_for_command(message, chat_id, user_id).await?)
+}
+
+// whether a user may press a button on the bot's reply to a restricting command,
+// going by the same rules as the command it replied to
+pub async fn can_restrict_from_reply(
+    bot: &crate::Bot,
+    reply: &Message,
+    user_id: i64,
+) -> anyhow::Result<bool> {
+    match reply.reply_to_message() {
+        Some(command_msg) => can_restrict_in_chat(bot, command_msg, reply.chat.id, user_id).await,
+        None => can_user_restrict_members(bot, reply.chat.id, user_id).await,
+    }
+}
+
+pub async fn can_user_delete_messages(
+    bot: &crate::Bot,
+    chat_id: i64,
+    user_id: i64,
+) -> anyhow::Result<bool> {
+    let chat_member = admincache::get_admin(bot, chat_id, user_id).await?;
+
+    Ok(match chat_member.map(|member| member.kind) {
+        Some(ChatMemberKind::Owner(_)) => true,
+        Some(ChatMemberKind::Administrator(adm)) => adm.can_delete_messages,
+        _ => false,
+    })
+}
+
+// whether a user holds a given admin right in a chat, eg: ChatMemberKind::can_pin_messages
+pub async fn has_admin_right(
+    bot: &crate::Bot,
+    chat_id: i64,
+    user_id: i64,
+    right: fn(&ChatMemberKind) -> bool,
+) -> anyhow::Result<bool> {
+    let chat_member = admincache::get_admin(bot, chat_id, user_id).await?;
+
+    Ok(chat_member.map_or(false, |member| right(&member.kind)))
+}
+
+pub async fn is_chat_admin(bot: &crate::Bot, chat_id: i64, user_id: i64) -> anyhow::Result<bool> {
+    Ok(admincache::get_admin(bot, chat_id, user_id)
+        .await?
+        .is_some())
+}